@@ -0,0 +1,290 @@
+use crate::engine::capabilities::{detect, MIN_VERSION};
+use crate::engine::error::FfmpegError;
+use crate::engine::jobs::{with_reporter, Reporter};
+use crate::engine::{free_path, same_file, set_binaries};
+use crate::operations::{self, Inputs};
+use crate::presets::{self, Preset};
+use crate::queue::JobOutput;
+use crate::settings;
+use serde_json::{Map, Value};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+const USAGE: &str = "Usage: ffmpeg-owl-ui cli <operation> --input <file>... --output <path> [options]
+
+Operations:
+  mute, rotate, speed, animation, frames, slideshow, extract_audio, normalize, analyze,
+  adjust_audio, remove_silence, channels, streams, convert, chain, custom
+
+Options:
+  -i, --input <file>        Input file, repeat it to process several files
+  -o, --output <path>       Output file, or the output folder when there are several inputs
+  -p, --preset <name>       Load a saved preset
+      --preset-file <file>  Load a preset from a JSON file
+  -s, --set <key>=<value>   Override one preset key, the value is JSON or a plain string
+      --overwrite           Replace outputs that already exist
+      --rename              Write next to existing outputs as \"name (2).ext\"
+  -v, --verbose             Print the ffmpeg log
+  -h, --help                Show this help
+
+Several inputs are named with the preset's batch_template. Slideshows take the
+images as inputs, streams adds the tracks of every input after the first.
+
+Exit codes: 0 success, 1 an operation failed, 2 invalid arguments or settings,
+3 ffmpeg is missing or lacks a required feature.";
+
+#[derive(Debug, Default)]
+struct Args {
+    operation: String,
+    inputs: Vec<String>,
+    output: Option<String>,
+    preset: Option<String>,
+    preset_file: Option<String>,
+    values: Map<String, Value>,
+    overwrite: bool,
+    rename: bool,
+    verbose: bool,
+}
+
+fn parse(args: &[String]) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "-i" | "--input" => parsed.inputs.push(value()?),
+            "-o" | "--output" => parsed.output = Some(value()?),
+            "-p" | "--preset" => parsed.preset = Some(value()?),
+            "--preset-file" => parsed.preset_file = Some(value()?),
+            "-s" | "--set" => {
+                let pair = value()?;
+                let Some((key, value)) = pair.split_once('=') else {
+                    return Err(format!("Invalid --set {}, use key=value", pair));
+                };
+                let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+                parsed.values.insert(key.trim().to_string(), value);
+            }
+            "--overwrite" => parsed.overwrite = true,
+            "--rename" => parsed.rename = true,
+            "-v" | "--verbose" => parsed.verbose = true,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ if parsed.operation.is_empty() => parsed.operation = arg.clone(),
+            _ => return Err(format!("Unexpected argument {}", arg)),
+        }
+    }
+    if parsed.operation.is_empty() {
+        return Err("No operation given".to_string());
+    }
+    if parsed.inputs.is_empty() {
+        return Err("No input given".to_string());
+    }
+    if parsed.overwrite && parsed.rename {
+        return Err("--overwrite and --rename exclude each other".to_string());
+    }
+    if parsed.output.is_none() && parsed.operation != "analyze" {
+        return Err("No output given".to_string());
+    }
+    Ok(parsed)
+}
+
+// Runs `ffmpeg-owl-ui cli ...`, `args` are the arguments after "cli".
+// The operations are built from a preset the same way the window builds them.
+pub fn run(args: &[String]) -> ExitCode {
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let args = match parse(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    if !operations::ACTIONS.contains(&args.operation.as_str()) {
+        eprintln!("error: Unknown operation {}\n\n{}", args.operation, USAGE);
+        return ExitCode::from(2);
+    }
+
+    set_binaries(settings::load().binaries);
+    let capabilities = match detect() {
+        Ok(capabilities) => capabilities,
+        Err(e) => {
+            eprintln!("error: ffmpeg couldn't be started: {}", e);
+            return ExitCode::from(3);
+        }
+    };
+    if !capabilities.is_supported() {
+        eprintln!(
+            "warning: ffmpeg {} is older than {}.{}, some operations will fail",
+            capabilities.version, MIN_VERSION.0, MIN_VERSION.1
+        );
+    }
+
+    // the preset, then the --set values, then the operation on the command line
+    let base = Preset::initial();
+    let keys = serde_json::to_value(&base).unwrap_or_default();
+    if let Some(key) = args.values.keys().find(|key| keys.get(key.as_str()).is_none()) {
+        eprintln!("error: Unknown preset key {}", key);
+        return ExitCode::from(2);
+    }
+    let preset = match (&args.preset, &args.preset_file) {
+        (Some(name), _) => presets::find(name).and_then(|saved| presets::load(&saved, &base)),
+        (None, Some(file)) => presets::read_file(Path::new(file), &base),
+        (None, None) => Ok(base),
+    };
+    // numbers typed for text settings stay text: -s anim_fps=12
+    let values = args.values.iter().map(|(key, value)| match value {
+        Value::Number(_) | Value::Bool(_) if keys[key.as_str()].is_string() => (key.clone(), Value::String(value.to_string())),
+        _ => (key.clone(), value.clone()),
+    });
+    let preset = preset.and_then(|preset| presets::merge(&preset, values.collect()));
+    let mut preset = match preset {
+        Ok(preset) => preset,
+        Err(e) => {
+            eprintln!("error: Unable to load the preset: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+    preset.action = args.operation.clone();
+
+    let output = args.output.clone().unwrap_or_else(|| "-".to_string());
+    let batch = args.inputs.len() > 1 && !operations::combines_inputs(&preset.action);
+    let inputs = if preset.action == "slideshow" {
+        Inputs {
+            slide_images: args.inputs.clone(),
+            ..Inputs::default()
+        }
+    } else {
+        // the first file stands in for a batch, like in the window
+        Inputs::probed(&args.inputs[0]).unwrap_or_else(|e| {
+            eprintln!("warning: Unable to read the streams of {}: {}", args.inputs[0], e);
+            Inputs {
+                source: args.inputs[0].clone(),
+                ..Inputs::default()
+            }
+        })
+    };
+    let mut inputs = inputs;
+    if preset.action == "streams" {
+        for file in &args.inputs[1..] {
+            if let Err(e) = inputs.add_track_input(file) {
+                eprintln!("error: Unable to read the tracks of {}: {}", file, e);
+                return ExitCode::from(2);
+            }
+        }
+    }
+
+    let missing = capabilities.missing(&operations::requirements(&preset, &inputs));
+    if !missing.is_empty() {
+        eprintln!("error: Your ffmpeg build lacks {}", missing.join(", "));
+        return ExitCode::from(3);
+    }
+    if batch && preset.batch_template.trim().is_empty() {
+        eprintln!("error: Invalid filename template");
+        return ExitCode::from(2);
+    }
+    if !batch && !matches!(preset.action.as_str(), "frames" | "analyze") && Path::new(&output).is_dir() {
+        eprintln!("error: {} is a folder, name the output file", output);
+        return ExitCode::from(2);
+    }
+    let operation = match operations::operation(&preset, &inputs) {
+        Ok(operation) => operation,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    // the batch folder only appears once everything is valid
+    if batch {
+        if let Err(e) = fs::create_dir_all(&output) {
+            eprintln!("error: Unable to create {}: {}", output, e);
+            return ExitCode::from(2);
+        }
+    }
+
+    let jobs: Vec<(String, String)> = if batch {
+        let files = args.inputs.iter().enumerate();
+        files.map(|(index, input)| (input.clone(), operations::batch_output(&preset, &inputs, &output, input, index))).collect()
+    } else if preset.action == "slideshow" {
+        vec![(format!("{} images", args.inputs.len()), output)]
+    } else {
+        vec![(args.inputs[0].clone(), output)]
+    };
+
+    // nothing is replaced unless asked for, like the confirmation in the window
+    let jobs = if args.rename {
+        jobs.into_iter().map(|(input, output)| (input, free_path(&output))).collect()
+    } else {
+        jobs
+    };
+    let taken: Vec<&str> = jobs
+        .iter()
+        .map(|(_, output)| output.as_str())
+        .filter(|output| operations::output_taken(&preset.action, output))
+        .collect();
+    if !args.overwrite && !taken.is_empty() {
+        eprintln!("error: {} already exists, pass --overwrite or --rename", taken.join(", "));
+        return ExitCode::from(2);
+    }
+
+    let mut failed = 0;
+    for (input, output) in jobs {
+        if same_file(&input, &output) {
+            eprintln!("error: Skipped {}, the output would overwrite it", input);
+            failed += 1;
+            continue;
+        }
+        if preset.action == "frames" {
+            if let Err(e) = fs::create_dir_all(&output) {
+                eprintln!("error: Unable to create {}: {}", output, e);
+                failed += 1;
+                continue;
+            }
+        }
+        let label = format!("{} {}", preset.action, input);
+        let verbose = args.verbose;
+        let progress_label = label.clone();
+        let reporter = Reporter {
+            on_progress: Box::new(move |value| {
+                eprint!("\r{} {:5.1}%", progress_label, value);
+                let _ = io::stderr().flush();
+            }),
+            on_log: Box::new(move |line| {
+                if verbose {
+                    eprintln!("\r{}", line);
+                }
+            }),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        };
+        eprint!("{}", label);
+        let result = with_reporter(reporter, || operation(&input, &output));
+        eprintln!();
+        match result {
+            Ok(JobOutput::Message(message)) => eprintln!("{}", message),
+            Ok(JobOutput::Report(report)) => match serde_json::to_string_pretty(&report) {
+                Ok(json) => println!("{}", json),
+                Err(e) => eprintln!("error: {}", e),
+            },
+            Err(e) => {
+                let error = FfmpegError::from_io(&e);
+                eprintln!("error: {} failed: {}", label, error.message);
+                // the log was already streamed with --verbose
+                if !verbose && !error.details.is_empty() {
+                    eprintln!("{}", error.details);
+                }
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
@@ -1,84 +1,905 @@
-use std::io::{self, BufRead, BufReader};
+pub mod audio;
+pub mod capabilities;
+pub mod chain;
+pub mod command;
+pub mod convert;
+pub mod custom;
+pub mod encoding;
+pub mod error;
+pub mod jobs;
+pub mod loudness;
+pub mod probe;
+pub mod streams;
+
+use std::fmt;
+use std::fs;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use encoding::{AudioCodec, EncodingSettings};
+use error::FfmpegError;
+use serde::{Deserialize, Serialize};
+
+lazy_static::lazy_static! {
+    static ref BINARIES: RwLock<Binaries> = RwLock::new(Binaries::default());
+}
+
+// Executables the engine runs, bare names are looked up in PATH.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Binaries {
+    pub ffmpeg: String,
+    pub ffprobe: String,
+}
+
+impl Default for Binaries {
+    fn default() -> Self {
+        Binaries {
+            ffmpeg: "ffmpeg".to_string(),
+            ffprobe: "ffprobe".to_string(),
+        }
+    }
+}
+
+pub fn set_binaries(binaries: Binaries) {
+    *BINARIES.write().unwrap() = binaries;
+}
+
+// A temp file path of its own for every call, jobs run in parallel threads of one process
+fn temp_file(kind: &str, extension: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("ffmpeg-owl-{}-{}-{}.{}", kind, std::process::id(), n, extension))
+}
+
+fn ffmpeg() -> Command {
+    Command::new(&BINARIES.read().unwrap().ffmpeg)
+}
+
+fn ffprobe() -> Command {
+    Command::new(&BINARIES.read().unwrap().ffprobe)
+}
+
+pub fn mute(input: &str, output: &str) -> Result<(), io::Error> {
+    let args: Vec<String> = vec![
+        "-i".into(),
+        input.into(),
+        "-c".into(),
+        "copy".into(),
+        "-an".into(),
+        output.into(),
+        "-n".into(),
+    ];
+    run_ffmpeg(&args)
+}
+
+pub fn rotate(input: &str, output_file: &str, mode: &str, encoding: &EncodingSettings) -> Result<(), io::Error> {
+    let mut args: Vec<String> = vec!["-hide_banner".into(), "-i".into(), input.into(), "-vf".into(), mode.into()];
+    args.extend(encoding.video_args(true)?);
+    args.push(output_file.into());
+    args.push("-n".into());
+    run_ffmpeg(&args)
+}
+
+pub fn speed(
+    input: &str,
+    output_file: &str,
+    factor: f64,
+    drop_audio: bool,
+    encoding: &EncodingSettings,
+) -> Result<(), io::Error> {
+    if !factor.is_finite() || factor <= 0.0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid speed factor: {}", factor),
+        ));
+    }
 
-pub fn mute(input: &str, output: &str) -> Result<(), std::io::Error> {
-    let output = Command::new("ffmpeg")
-        .arg("-i")
-        .arg(input) // No need to quote here
-        .arg("-c")
-        .arg("copy")
-        .arg("-an")
-        .arg(output)
-        .arg("-y") // No need to quote here
-        .output()?;
-
-    if output.status.success() {
-        Ok(())
+    let mut args: Vec<String> = vec![
+        "-hide_banner".into(),
+        "-i".into(),
+        input.into(),
+        "-filter:v".into(),
+        format!("setpts=PTS/{}", factor),
+    ];
+    if drop_audio {
+        args.push("-an".into());
     } else {
-        Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            String::from_utf8_lossy(&output.stderr),
-        ))
+        args.push("-filter:a".into());
+        args.push(atempo_chain(factor));
+        args.extend(encoding.audio_args(true)?);
+    }
+    args.extend(encoding.video_args(true)?);
+    args.push(output_file.into());
+    args.push("-n".into());
+
+    run_ffmpeg(&args)
+}
+
+// atempo only accepts 0.5..=2.0 per instance on older ffmpeg builds, so bigger
+// changes are split into several chained filters (e.g. 4x => 2.0 * 2.0).
+fn atempo_chain(factor: f64) -> String {
+    let mut remaining = factor;
+    let mut filters = vec![];
+    while remaining > 2.0 {
+        filters.push("atempo=2.0".to_string());
+        remaining /= 2.0;
+    }
+    while remaining < 0.5 {
+        filters.push("atempo=0.5".to_string());
+        remaining /= 0.5;
+    }
+    filters.push(format!("atempo={}", remaining));
+    filters.join(",")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum AnimationFormat {
+    #[default]
+    Gif,
+    WebP,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Dither {
+    Bayer,
+    FloydSteinberg,
+    Sierra2,
+    #[default]
+    Sierra2_4a,
+    None,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationOptions {
+    pub format: AnimationFormat,
+    pub fps: u32,
+    pub width: u32,
+    pub dither: Dither,
+    pub loop_count: u32, // 0 = loop forever
+    pub start: Option<String>,
+    pub end: Option<String>,
+}
+
+// Returns the size of the written file in bytes.
+pub fn animation(input: &str, output_file: &str, options: &AnimationOptions) -> Result<u64, io::Error> {
+    if options.fps == 0 || options.width == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid frame rate or width"));
+    }
+    let mut range: Vec<String> = vec![];
+    if let Some(start) = &options.start {
+        range.push("-ss".into());
+        range.push(start.clone());
+    }
+    if let Some(end) = &options.end {
+        range.push("-to".into());
+        range.push(end.clone());
+    }
+    let filters = format!("fps={},scale={}:-1:flags=lanczos", options.fps, options.width);
+
+    let mut args: Vec<String> = vec!["-hide_banner".into()];
+    args.extend(range.iter().cloned());
+    args.push("-i".into());
+    args.push(input.into());
+
+    match options.format {
+        AnimationFormat::Gif => {
+            // first pass builds an optimized 256 color palette for the selected range,
+            // the second pass maps every frame onto it
+            let palette = temp_file("palette", "png");
+            let palette = palette.to_string_lossy().to_string();
+
+            let mut first: Vec<String> = args.clone();
+            first.push("-vf".into());
+            first.push(format!("{},palettegen=stats_mode=diff", filters));
+            first.push(palette.clone());
+            first.push("-y".into());
+            run_ffmpeg(&first)?;
+
+            args.push("-i".into());
+            args.push(palette.clone());
+            args.push("-lavfi".into());
+            args.push(format!("{} [x]; [x][1:v] paletteuse=dither={}", filters, options.dither.name()));
+            args.push("-loop".into());
+            args.push(gif_loop(options.loop_count));
+            args.push(output_file.into());
+            args.push("-n".into());
+            let result = run_ffmpeg(&args);
+            let _ = fs::remove_file(&palette);
+            result?;
+        }
+        AnimationFormat::WebP => {
+            args.push("-vf".into());
+            args.push(filters);
+            args.push("-an".into());
+            args.push("-loop".into());
+            args.push(options.loop_count.to_string());
+            args.push(output_file.into());
+            args.push("-n".into());
+            run_ffmpeg(&args)?;
+        }
+    }
+
+    // a recorded run wrote nothing
+    if command::recording() {
+        return Ok(0);
+    }
+    Ok(fs::metadata(output_file)?.len())
+}
+
+// The gif muxer counts repeats, not plays: -1 plays once, 0 loops forever.
+fn gif_loop(loop_count: u32) -> String {
+    match loop_count {
+        0 => "0".to_string(),
+        1 => "-1".to_string(),
+        n => (n - 1).to_string(),
+    }
+}
+
+impl Dither {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Dither::Bayer => "bayer",
+            Dither::FloydSteinberg => "floyd_steinberg",
+            Dither::Sierra2 => "sierra2",
+            Dither::Sierra2_4a => "sierra2_4a",
+            Dither::None => "none",
+        }
     }
 }
 
-pub fn rotate(input: &str, output_file: &str, mode: &str) -> Result<(), io::Error> {
-    let output = Command::new("mediainfo")
-        .arg("--Output=Video;%FrameCount%")
-        .arg(input)
-        .output()?;
+impl fmt::Display for Dither {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Dither::Bayer => "Bayer (ordered)",
+            Dither::FloydSteinberg => "Floyd-Steinberg",
+            Dither::Sierra2 => "Sierra 2",
+            Dither::Sierra2_4a => "Sierra 2-4A",
+            Dither::None => "None",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl AnimationFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::WebP => "webp",
+        }
+    }
+}
+
+impl fmt::Display for AnimationFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            AnimationFormat::Gif => "GIF",
+            AnimationFormat::WebP => "Animated WebP",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    Jpg,
+    WebP,
+}
+
+#[derive(Debug, Clone)]
+pub enum FrameSelection {
+    EveryFrame,
+    Interval(f64), // one frame every N seconds
+    Keyframes,
+    Single(String), // timestamp
+}
 
-    if !output.status.success() {
+// Writes the selected frames into `output_dir` as `<pattern>.<ext>`, the pattern
+// may contain a printf style counter like `frame_%04d`.
+pub fn extract_frames(
+    input: &str,
+    output_dir: &str,
+    pattern: &str,
+    format: ImageFormat,
+    selection: &FrameSelection,
+) -> Result<(), io::Error> {
+    if !Path::new(output_dir).is_dir() {
         return Err(io::Error::new(
-            io::ErrorKind::Other,
-            String::from_utf8_lossy(&output.stderr),
+            io::ErrorKind::NotFound,
+            format!("Output folder does not exist: {}", output_dir),
         ));
     }
 
-    let total_frames = String::from_utf8_lossy(&output.stdout).trim().parse::<u64>().unwrap();
-    println!("Total frames: {}", total_frames);
-
-    let mut child = Command::new("ffmpeg")
-        // .arg("-v")
-        // .arg("warning")
-        .arg("-hide_banner")
-        // .arg("-stats")
-        .arg("-i")
-        .arg(input)
-        .arg("-vf")
-        .arg(mode)
-        // .arg("-progress")
-        // .arg("-")
-        .arg("-loglevel")
-        .arg("debug")
-        .arg(output_file)
-        .arg("-y")
-        .stdout(Stdio::piped())
+    let mut args: Vec<String> = vec!["-hide_banner".into()];
+    match selection {
+        FrameSelection::Keyframes => {
+            args.push("-skip_frame".into());
+            args.push("nokey".into());
+        }
+        FrameSelection::Single(timestamp) => {
+            args.push("-ss".into());
+            args.push(timestamp.clone());
+        }
+        _ => {}
+    }
+    args.push("-i".into());
+    args.push(input.into());
+
+    match selection {
+        FrameSelection::EveryFrame => {}
+        FrameSelection::Interval(seconds) => {
+            if !seconds.is_finite() || *seconds <= 0.0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Invalid frame interval: {}", seconds),
+                ));
+            }
+            args.push("-vf".into());
+            args.push(format!("fps=1/{}", seconds));
+        }
+        FrameSelection::Keyframes => {
+            args.push("-fps_mode".into());
+            args.push("passthrough".into());
+        }
+        FrameSelection::Single(_) => {
+            // the image muxer numbers the one frame like the others: frame_0001.png
+            args.push("-frames:v".into());
+            args.push("1".into());
+        }
+    }
+    if format == ImageFormat::Jpg {
+        args.push("-q:v".into());
+        args.push("2".into());
+    }
+
+    let file_name = format!("{}.{}", pattern, format.extension());
+    args.push(Path::new(output_dir).join(file_name).to_string_lossy().to_string());
+    // frames are many files in a folder, replacing them was confirmed by the caller
+    args.push("-y".into());
+
+    run_ffmpeg(&args)
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpg => "jpg",
+            ImageFormat::WebP => "webp",
+        }
+    }
+}
+
+impl fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            ImageFormat::Png => "PNG",
+            ImageFormat::Jpg => "JPG",
+            ImageFormat::WebP => "WebP",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum SlideshowMode {
+    #[default]
+    Sequence,
+    Slideshow,
+}
+
+#[derive(Debug, Clone)]
+pub struct SlideshowOptions {
+    pub mode: SlideshowMode,
+    pub fps: u32,
+    pub width: u32,
+    pub height: u32,
+    pub duration: f64,  // seconds per image, slideshow only
+    pub crossfade: f64, // seconds, slideshow only
+    pub ken_burns: bool,
+    pub audio: Option<String>,
+}
+
+const IMAGE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "webp", "bmp", "tiff"];
+
+// Lists the images of a folder sorted by file name.
+pub fn list_images(dir: &str) -> Result<Vec<String>, io::Error> {
+    list_files(dir, &IMAGE_EXTENSIONS)
+}
+
+// Lists the files of a folder with one of `extensions`, sorted by file name.
+pub fn list_files(dir: &str, extensions: &[&str]) -> Result<Vec<String>, io::Error> {
+    let mut files: Vec<String> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| extensions.contains(&ext.to_lowercase().as_str()))
+        })
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+pub fn slideshow(
+    images: &[String],
+    output_file: &str,
+    options: &SlideshowOptions,
+    encoding: &EncodingSettings,
+) -> Result<(), io::Error> {
+    if images.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "No images selected"));
+    }
+    if options.fps == 0 || options.width == 0 || options.height == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid frame rate or size"));
+    }
+
+    // every image is letterboxed into the output size so mixed resolutions line up
+    let fit = format!(
+        "scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,setsar=1",
+        w = options.width,
+        h = options.height
+    );
+
+    // inputs go first, the filters can only be added once all of them are declared
+    let mut args: Vec<String> = vec!["-hide_banner".into()];
+    let mut out_args: Vec<String> = vec![];
+    let mut list_file = None;
+
+    match options.mode {
+        SlideshowMode::Sequence => {
+            // the concat demuxer takes arbitrary file names, unlike the image2 pattern
+            let list = temp_file("sequence", "txt");
+            let frame = 1.0 / options.fps as f64;
+            let mut content = String::new();
+            for image in images {
+                content.push_str(&format!("file '{}'\nduration {}\n", image.replace('\'', "'\\''"), frame));
+            }
+            // the last duration is only honoured when the file is repeated
+            content.push_str(&format!("file '{}'\n", images[images.len() - 1].replace('\'', "'\\''")));
+            fs::write(&list, content)?;
+
+            args.extend(["-f", "concat", "-safe", "0", "-i"].map(String::from));
+            args.push(list.to_string_lossy().to_string());
+            out_args.push("-vf".into());
+            out_args.push(format!("{},format=yuv420p", fit));
+            list_file = Some(list);
+        }
+        SlideshowMode::Slideshow => {
+            if options.duration <= 0.0 || options.crossfade < 0.0 || options.crossfade >= options.duration {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Crossfade must be shorter than the image duration",
+                ));
+            }
+            // each clip is stretched by the crossfade so it overlaps with the next one
+            let frames = ((options.duration + options.crossfade) * options.fps as f64).round() as u32;
+            if frames == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "The image duration is shorter than one frame",
+                ));
+            }
+            let mut graph: Vec<String> = vec![];
+            for (i, image) in images.iter().enumerate() {
+                args.push("-i".into());
+                args.push(image.clone());
+
+                let motion = if options.ken_burns {
+                    format!(
+                        "zoompan=z='min(zoom+0.0015,1.3)':x='iw/2-(iw/zoom/2)':y='ih/2-(ih/zoom/2)':d={}:s={}x{}:fps={}",
+                        frames, options.width, options.height, options.fps
+                    )
+                } else {
+                    format!("loop=loop={}:size=1:start=0,setpts=N/{}/TB", frames - 1, options.fps)
+                };
+                graph.push(format!("[{}:v]{},{},format=yuv420p[v{}]", i, fit, motion, i));
+            }
+
+            let last = if options.crossfade > 0.0 {
+                let mut last = "v0".to_string();
+                for i in 1..images.len() {
+                    graph.push(format!(
+                        "[{}][v{}]xfade=transition=fade:duration={}:offset={}[x{}]",
+                        last,
+                        i,
+                        options.crossfade,
+                        i as f64 * options.duration,
+                        i
+                    ));
+                    last = format!("x{}", i);
+                }
+                last
+            } else {
+                let inputs: String = (0..images.len()).map(|i| format!("[v{}]", i)).collect();
+                graph.push(format!("{}concat=n={}:v=1:a=0[out]", inputs, images.len()));
+                "out".to_string()
+            };
+
+            out_args.push("-filter_complex".into());
+            out_args.push(graph.join(";"));
+            out_args.push("-map".into());
+            out_args.push(format!("[{}]", last));
+        }
+    }
+
+    if let Some(audio) = &options.audio {
+        let audio_index = match options.mode {
+            SlideshowMode::Sequence => 1,
+            SlideshowMode::Slideshow => images.len(),
+        };
+        args.push("-i".into());
+        args.push(audio.clone());
+        if options.mode == SlideshowMode::Sequence {
+            out_args.push("-map".into());
+            out_args.push("0:v".into());
+        }
+        out_args.push("-map".into());
+        out_args.push(format!("{}:a", audio_index));
+        // aac stays the default for the soundtrack, it fits every output extension offered
+        match encoding.audio_codec {
+            AudioCodec::Auto => out_args.extend(["-c:a".into(), "aac".into()]),
+            _ => out_args.extend(encoding.audio_args(false)?),
+        }
+        out_args.push("-shortest".into());
+    }
+    out_args.extend(encoding.video_args(true)?);
+    args.extend(out_args);
+    args.push("-r".into());
+    args.push(options.fps.to_string());
+    args.push(output_file.into());
+    args.push("-n".into());
+
+    let result = run_ffmpeg(&args);
+    if let Some(list) = list_file {
+        let _ = fs::remove_file(list);
+    }
+    result
+}
+
+impl fmt::Display for SlideshowMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            SlideshowMode::Sequence => "Image sequence (fixed frame rate)",
+            SlideshowMode::Slideshow => "Slideshow",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum AudioTarget {
+    #[default]
+    Copy,
+    Mp3,
+    Flac,
+    Wav,
+    Opus,
+}
+
+// Extracts one audio stream, `stream_index` is the absolute index from the probe.
+pub fn extract_audio(
+    input: &str,
+    output_file: &str,
+    stream_index: usize,
+    target: AudioTarget,
+    bitrate_kbps: u32,
+) -> Result<(), io::Error> {
+    let mut args: Vec<String> = vec![
+        "-hide_banner".into(),
+        "-i".into(),
+        input.into(),
+        "-map".into(),
+        format!("0:{}", stream_index),
+        "-vn".into(),
+        "-c:a".into(),
+    ];
+    match target {
+        AudioTarget::Copy => args.push("copy".into()),
+        AudioTarget::Mp3 => args.extend(["libmp3lame".into(), "-b:a".into(), format!("{}k", bitrate_kbps)]),
+        AudioTarget::Flac => args.push("flac".into()),
+        AudioTarget::Wav => args.push("pcm_s16le".into()),
+        AudioTarget::Opus => args.extend(["libopus".into(), "-b:a".into(), format!("{}k", bitrate_kbps)]),
+    }
+    args.push(output_file.into());
+    args.push("-n".into());
+
+    run_ffmpeg(&args)
+}
+
+// Container matching a codec for stream copies, Matroska takes everything else.
+pub fn audio_copy_extension(codec: &str) -> &'static str {
+    match codec {
+        "aac" | "alac" => "m4a",
+        "mp3" => "mp3",
+        "opus" | "vorbis" => "ogg",
+        "ac3" => "ac3",
+        "eac3" => "eac3",
+        "flac" => "flac",
+        "dts" => "dts",
+        c if c.starts_with("pcm_") => "wav",
+        _ => "mka",
+    }
+}
+
+impl AudioTarget {
+    pub fn extension(&self, codec: &str) -> &'static str {
+        match self {
+            AudioTarget::Copy => audio_copy_extension(codec),
+            AudioTarget::Mp3 => "mp3",
+            AudioTarget::Flac => "flac",
+            AudioTarget::Wav => "wav",
+            AudioTarget::Opus => "opus",
+        }
+    }
+
+    pub fn has_bitrate(&self) -> bool {
+        matches!(self, AudioTarget::Mp3 | AudioTarget::Opus)
+    }
+}
+
+impl fmt::Display for AudioTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            AudioTarget::Copy => "Copy (no re-encode)",
+            AudioTarget::Mp3 => "MP3",
+            AudioTarget::Flac => "FLAC",
+            AudioTarget::Wav => "WAV",
+            AudioTarget::Opus => "Opus",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// Runs `write` against a hidden file next to `output` and moves it into place only
+// once it succeeds, so a failed or cancelled run never leaves a half written output
+// and an existing one is replaced in a single step. Refuses to replace the input.
+pub fn write_atomically<T>(
+    input: &str,
+    output: &str,
+    write: impl FnOnce(&str) -> Result<T, io::Error>,
+) -> Result<T, io::Error> {
+    if same_file(input, output) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "The output would overwrite the input"));
+    }
+    let path = Path::new(output);
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    // the extension stays last, ffmpeg picks the muxer from it
+    let temp = path.with_file_name(format!(".partial-{}", name)).to_string_lossy().to_string();
+    if command::recording() {
+        return command::shown_as(&temp, output, || write(&temp));
+    }
+
+    let _ = fs::remove_file(&temp); // left over by a crash
+    let result = command::shown_as(&temp, output, || write(&temp));
+    match result {
+        Ok(value) => {
+            fs::rename(&temp, output)?;
+            Ok(value)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            Err(e)
+        }
+    }
+}
+
+pub fn same_file(a: &str, b: &str) -> bool {
+    // the output may not exist yet, its folder does
+    let canonical = |path: &str| {
+        let path = Path::new(path);
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        fs::canonicalize(dir).ok().zip(path.file_name()).map(|(dir, name)| dir.join(name))
+    };
+    a == b || canonical(a).is_some_and(|a| Some(a) == canonical(b))
+}
+
+// `path` if it is free, otherwise the first free "name (2).ext", "name (3).ext", ...
+pub fn free_path(path: &str) -> String {
+    let original = Path::new(path);
+    if !original.exists() {
+        return path.to_string();
+    }
+    let stem = original.file_stem().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let ext = original.extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+    (2..)
+        .map(|n| original.with_file_name(format!("{} ({}){}", stem, n, ext)))
+        .find(|candidate| !candidate.exists())
+        .map(|candidate| candidate.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+fn run_ffmpeg(args: &[String]) -> Result<(), io::Error> {
+    run_ffmpeg_stderr(args).map(|_| ())
+}
+
+// Like run_ffmpeg, but hands back stderr where ffmpeg prints filter reports.
+// stderr is read as it comes so a running job sees progress and can be cancelled.
+fn run_ffmpeg_stderr(args: &[String]) -> Result<String, io::Error> {
+    let Some(command) = command::planned(args) else {
+        return Ok(String::new());
+    };
+    let mut child = Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
         .stderr(Stdio::piped())
         .spawn()?;
+    let child_stderr = child.stderr.take().expect("Unable to pipe stderr");
 
-    let child_stderr = child.stderr.as_mut().expect("Unable to pipe stderr");
-    let mut reader = BufReader::new(child_stderr);
-    let mut buff = String::new();
-    while reader.read_line(&mut buff).expect("Unable to read chunk") > 0 {
-        // buff will contain only one line
-        if buff.contains("frame=") {
-            let frame_index = buff.find("frame=").unwrap();
-            let frame_number = buff[frame_index + 6..].split_whitespace().next().unwrap();
-            let current_frame = frame_number.trim().parse::<u64>().unwrap();
-            let percentage = (current_frame as f64 / total_frames as f64) * 100.0;
-            println!("current: {}, total: {}, {:.2}%", current_frame, total_frames, percentage);
+    // passes run with -nostats can stay silent for minutes, so the cancel flag
+    // is watched next to the read loop instead of after each line
+    let child = Arc::new(Mutex::new(child));
+    let finished = Arc::new(AtomicBool::new(false));
+    let watcher = jobs::cancel_flag().map(|cancelled| {
+        let (child, finished) = (child.clone(), finished.clone());
+        thread::spawn(move || {
+            while !finished.load(Ordering::Relaxed) {
+                if cancelled.load(Ordering::Relaxed) {
+                    let _ = child.lock().unwrap().kill();
+                    return;
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+        })
+    });
+
+    let mut stderr = String::new();
+    let mut parser = jobs::ProgressParser::default();
+    let mut line: Vec<u8> = vec![];
+    let mut read_error = None;
+    // progress lines end with \r, everything else with \n
+    for byte in BufReader::new(child_stderr).bytes() {
+        let byte = match byte {
+            Ok(byte) => byte,
+            Err(e) => {
+                read_error = Some(e);
+                break;
+            }
+        };
+        if byte != b'\n' && byte != b'\r' {
+            line.push(byte);
+            continue;
+        }
+        if line.is_empty() {
+            continue;
         }
-        buff.clear();
+        let text = String::from_utf8_lossy(&line).to_string();
+        line.clear();
+        parser.feed(&text);
+        stderr.push_str(&text);
+        stderr.push('\n');
     }
+    stderr.push_str(&String::from_utf8_lossy(&line));
 
-    let output = child.wait_with_output()?;
-    if output.status.success() {
-        Ok(())
+    finished.store(true, Ordering::Relaxed);
+    if let Some(watcher) = watcher {
+        let _ = watcher.join();
+    }
+    let status = child.lock().unwrap().wait()?;
+    if jobs::is_cancelled() {
+        return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+    }
+    if let Some(e) = read_error {
+        return Err(e);
+    }
+    if status.success() {
+        Ok(stderr)
     } else {
-        Err(io::Error::new(
-            io::ErrorKind::Other,
-            String::from_utf8_lossy(&output.stderr),
-        ))
+        Err(FfmpegError::from_stderr(&stderr).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the tempo of every filter in the chain, e.g. [2.0, 2.0] for 4x
+    fn tempos(chain: &str) -> Vec<f64> {
+        chain.split(',').map(|filter| filter.strip_prefix("atempo=").unwrap().parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn atempo_keeps_small_changes_in_one_filter() {
+        assert_eq!(atempo_chain(1.5), "atempo=1.5");
+        assert_eq!(atempo_chain(2.0), "atempo=2");
+        assert_eq!(atempo_chain(0.5), "atempo=0.5");
+    }
+
+    #[test]
+    fn atempo_splits_big_changes() {
+        assert_eq!(atempo_chain(4.0), "atempo=2.0,atempo=2");
+        assert_eq!(atempo_chain(0.25), "atempo=0.5,atempo=0.5");
+        for factor in [0.1, 0.3, 3.0, 10.0, 16.0, 100.0] {
+            let tempos = tempos(&atempo_chain(factor));
+            assert!(tempos.iter().all(|tempo| (0.5..=2.0).contains(tempo)), "{}: {:?}", factor, tempos);
+            let product: f64 = tempos.iter().product();
+            assert!((product - factor).abs() < 1e-9, "{}: {:?}", factor, tempos);
+        }
+    }
+
+    #[test]
+    fn slideshow_refuses_images_shorter_than_a_frame() {
+        let options = SlideshowOptions {
+            mode: SlideshowMode::Slideshow,
+            fps: 1,
+            width: 640,
+            height: 360,
+            duration: 0.2,
+            crossfade: 0.0,
+            ken_burns: false,
+            audio: None,
+        };
+        let images = ["a.png".to_string(), "b.png".to_string()];
+        let error = slideshow(&images, "out.mp4", &options, &EncodingSettings::default()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("owl-engine-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn write_atomically_removes_the_partial_file_of_a_failed_run() {
+        let dir = temp_dir("failed");
+        let output = dir.join("out.mp4").to_string_lossy().to_string();
+        let result: Result<(), io::Error> = write_atomically("in.mp4", &output, |temp| {
+            assert!(temp.ends_with(".partial-out.mp4"));
+            fs::write(temp, "half written")?;
+            Err(io::Error::other("ffmpeg failed"))
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn write_atomically_moves_the_finished_file_into_place() {
+        let dir = temp_dir("finished");
+        let output = dir.join("out.mp4");
+        fs::write(&output, "old").unwrap();
+        let result = write_atomically("in.mp4", &output.to_string_lossy(), |temp| fs::write(temp, "new"));
+        assert!(result.is_ok());
+        assert_eq!(fs::read_to_string(&output).unwrap(), "new");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn the_input_is_never_the_output() {
+        // the tests run from the crate folder
+        assert!(same_file("src/main.rs", "./src/main.rs"));
+        assert!(same_file("src/main.rs", "src/../src/main.rs"));
+        let absolute = std::env::current_dir().unwrap().join("src/main.rs");
+        assert!(same_file(&absolute.to_string_lossy(), "src/main.rs"));
+        assert!(!same_file("src/main.rs", "src/cli.rs"));
+
+        let result = write_atomically("src/main.rs", "./src/main.rs", |_| -> Result<(), io::Error> {
+            panic!("the input would be overwritten")
+        });
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn free_path_numbers_the_taken_names() {
+        let dir = temp_dir("free");
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+        assert_eq!(free_path(&path("clip.mp4")), path("clip.mp4"));
+        fs::write(path("clip.mp4"), "").unwrap();
+        assert_eq!(free_path(&path("clip.mp4")), path("clip (2).mp4"));
+        fs::write(path("clip (2).mp4"), "").unwrap();
+        assert_eq!(free_path(&path("clip.mp4")), path("clip (3).mp4"));
+        // frame folders have no extension
+        fs::create_dir(path("frames")).unwrap();
+        assert_eq!(free_path(&path("frames")), path("frames (2)"));
     }
 }
@@ -0,0 +1,245 @@
+use super::encoding::EncodingSettings;
+use super::probe::probe;
+use super::{run_ffmpeg, run_ffmpeg_stderr};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AudioAdjust {
+    pub gain_db: f64,
+    pub fade_in: f64,  // seconds
+    pub fade_out: f64, // seconds
+    pub offset: f64,   // seconds, positive delays the audio, negative plays it earlier
+}
+
+// Applies gain, fades and a sync offset to the audio. The video stream is copied.
+pub fn adjust_audio(
+    input: &str,
+    output_file: &str,
+    adjust: &AudioAdjust,
+    encoding: &EncodingSettings,
+) -> Result<(), io::Error> {
+    let mut args: Vec<String> = vec!["-hide_banner".into(), "-i".into(), input.into()];
+    let mut filters: Vec<String> = vec![];
+
+    if adjust.offset < 0.0 {
+        // a negative input offset drops the head of the audio, adelay can't do that
+        args.extend(["-itsoffset".into(), adjust.offset.to_string(), "-i".into(), input.into()]);
+        args.extend(["-map".into(), "0:v?".into(), "-map".into(), "1:a".into()]);
+    } else {
+        args.extend(["-map".into(), "0:v?".into(), "-map".into(), "0:a".into()]);
+        if adjust.offset > 0.0 {
+            filters.push(format!("adelay={}:all=1", (adjust.offset * 1000.0).round() as u64));
+        }
+    }
+
+    if adjust.gain_db != 0.0 {
+        filters.push(format!("volume={}dB", adjust.gain_db));
+    }
+    if adjust.fade_in > 0.0 {
+        filters.push(format!("afade=t=in:st=0:d={}", adjust.fade_in));
+    }
+    if adjust.fade_out > 0.0 {
+        let duration = probe(input)?.duration().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "Unable to read the input duration")
+        })?;
+        // the fade has to finish where the (shifted) audio ends
+        let start = (duration + adjust.offset.min(0.0) - adjust.fade_out).max(0.0);
+        filters.push(format!("afade=t=out:st={}:d={}", start, adjust.fade_out));
+    }
+
+    args.extend(["-c:v".into(), "copy".into()]);
+    if filters.is_empty() {
+        args.extend(["-c:a".into(), "copy".into()]);
+    } else {
+        args.extend(["-af".into(), filters.join(",")]);
+        args.extend(encoding.audio_args(true)?);
+    }
+    args.push(output_file.into());
+    args.push("-n".into());
+
+    run_ffmpeg(&args)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SilentRange {
+    pub start: f64,
+    pub end: f64,
+}
+
+// Runs silencedetect and returns the silent ranges in seconds.
+pub fn detect_silence(input: &str, threshold_db: f64, min_duration: f64) -> Result<Vec<SilentRange>, io::Error> {
+    let stderr = run_ffmpeg_stderr(&[
+        "-hide_banner".into(),
+        "-nostats".into(),
+        "-i".into(),
+        input.into(),
+        "-vn".into(),
+        "-af".into(),
+        format!("silencedetect=noise={}dB:d={}", threshold_db, min_duration),
+        "-f".into(),
+        "null".into(),
+        "-".into(),
+    ])?;
+
+    let value = |line: &str, key: &str| -> Option<f64> {
+        let start = line.find(key)? + key.len();
+        line[start..].split_whitespace().next()?.parse().ok()
+    };
+
+    let mut ranges = vec![];
+    let mut start = None;
+    for line in stderr.lines() {
+        if let Some(s) = value(line, "silence_start:") {
+            start = Some(s.max(0.0));
+        } else if let Some(end) = value(line, "silence_end:") {
+            if let Some(start) = start.take() {
+                ranges.push(SilentRange { start, end });
+            }
+        }
+    }
+    // silence running until the end of the file has no silence_end line
+    if let Some(start) = start {
+        if let Some(end) = probe(input)?.duration() {
+            ranges.push(SilentRange { start, end });
+        }
+    }
+
+    Ok(ranges)
+}
+
+// Cuts the silent ranges out of video and audio. Each range is shrunk by `padding`
+// on both sides so the first and last syllables around a pause are kept.
+pub fn remove_silence(
+    input: &str,
+    output_file: &str,
+    silences: &[SilentRange],
+    padding: f64,
+    encoding: &EncodingSettings,
+) -> Result<(), io::Error> {
+    let probe = probe(input)?;
+    let duration = probe.duration().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "Unable to read the input duration")
+    })?;
+
+    let mut keep: Vec<(f64, f64)> = vec![];
+    let mut position = 0.0;
+    for silence in silences {
+        let (start, end) = (silence.start + padding, silence.end - padding);
+        if end <= start {
+            continue;
+        }
+        if start > position {
+            keep.push((position, start));
+        }
+        position = end;
+    }
+    if position < duration {
+        keep.push((position, duration));
+    }
+    if keep.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Nothing left after removing the silence"));
+    }
+
+    let expr = keep
+        .iter()
+        .map(|(start, end)| format!("between(t,{:.3},{:.3})", start, end))
+        .collect::<Vec<_>>()
+        .join("+");
+
+    let mut args: Vec<String> = vec!["-hide_banner".into(), "-i".into(), input.into()];
+    if !probe.streams_of("video").is_empty() {
+        args.push("-vf".into());
+        args.push(format!("select='{}',setpts=N/FRAME_RATE/TB", expr));
+        args.extend(encoding.video_args(true)?);
+    }
+    args.push("-af".into());
+    args.push(format!("aselect='{}',asetpts=N/SR/TB", expr));
+    args.extend(encoding.audio_args(true)?);
+    args.push(output_file.into());
+    args.push("-n".into());
+
+    run_ffmpeg(&args)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum ChannelMapping {
+    #[default]
+    MonoToStereo,
+    LeftToBoth,
+    RightToBoth,
+    SwapChannels,
+    DownmixStandard,
+    DownmixWithLfe,
+    DownmixDialogue,
+}
+
+// Remaps or downmixes the audio channels, `layout` is the probed channel layout of
+// the source (e.g. "5.1" or "5.1(side)") and decides which surround channels exist.
+pub fn remap_channels(
+    input: &str,
+    output_file: &str,
+    mapping: ChannelMapping,
+    layout: &str,
+    encoding: &EncodingSettings,
+) -> Result<(), io::Error> {
+    let (sl, sr) = if layout.contains("side") { ("SL", "SR") } else { ("BL", "BR") };
+    let filter = match mapping {
+        ChannelMapping::MonoToStereo => "channelmap=map=0-0|0-1:channel_layout=stereo".to_string(),
+        ChannelMapping::LeftToBoth => "pan=stereo|c0=FL|c1=FL".to_string(),
+        ChannelMapping::RightToBoth => "pan=stereo|c0=FR|c1=FR".to_string(),
+        ChannelMapping::SwapChannels => "channelmap=map=FL-FR|FR-FL".to_string(),
+        // '<' renormalizes the gains so the sum never clips
+        ChannelMapping::DownmixStandard => format!(
+            "pan=stereo|FL<FL+0.707*FC+0.707*{sl}|FR<FR+0.707*FC+0.707*{sr}"
+        ),
+        ChannelMapping::DownmixWithLfe => format!(
+            "pan=stereo|FL<FL+0.707*FC+0.707*{sl}+0.5*LFE|FR<FR+0.707*FC+0.707*{sr}+0.5*LFE"
+        ),
+        ChannelMapping::DownmixDialogue => format!(
+            "pan=stereo|FL<0.6*FL+1.2*FC+0.4*{sl}|FR<0.6*FR+1.2*FC+0.4*{sr}"
+        ),
+    };
+
+    let mut args: Vec<String> = vec![
+        "-hide_banner".into(),
+        "-i".into(),
+        input.into(),
+        "-af".into(),
+        filter,
+        "-c:v".into(),
+        "copy".into(),
+    ];
+    args.extend(encoding.audio_args(true)?);
+    args.push(output_file.into());
+    args.push("-n".into());
+
+    run_ffmpeg(&args)
+}
+
+impl ChannelMapping {
+    // A sensible starting point for a source with `channels` channels
+    pub fn for_channels(channels: u32) -> ChannelMapping {
+        match channels {
+            1 => ChannelMapping::MonoToStereo,
+            2 => ChannelMapping::LeftToBoth,
+            _ => ChannelMapping::DownmixStandard,
+        }
+    }
+}
+
+impl fmt::Display for ChannelMapping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            ChannelMapping::MonoToStereo => "Mono to stereo",
+            ChannelMapping::LeftToBoth => "Left channel to both",
+            ChannelMapping::RightToBoth => "Right channel to both",
+            ChannelMapping::SwapChannels => "Swap left and right",
+            ChannelMapping::DownmixStandard => "5.1 to stereo (ITU, no LFE)",
+            ChannelMapping::DownmixWithLfe => "5.1 to stereo (with LFE)",
+            ChannelMapping::DownmixDialogue => "5.1 to stereo (dialogue boost)",
+        };
+        write!(f, "{}", s)
+    }
+}
@@ -0,0 +1,292 @@
+use super::{ffmpeg, ffprobe, BINARIES};
+use std::collections::HashSet;
+use std::io;
+use std::process::Command;
+
+// Oldest release with every option the operations rely on (-fps_mode, xfade, ...)
+pub const MIN_VERSION: (u32, u32) = (5, 1);
+
+// What the installed ffmpeg build can do, read once at startup.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Capabilities {
+    pub version: String,
+    pub encoders: HashSet<String>,
+    pub filters: HashSet<String>,
+    pub muxers: HashSet<String>,
+}
+
+// Encoders, filters and muxers an operation needs from the build.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Requirements {
+    pub encoders: Vec<&'static str>,
+    pub filters: Vec<&'static str>,
+    pub muxers: Vec<&'static str>,
+}
+
+// Fails with NotFound when either binary can't be started.
+pub fn detect() -> Result<Capabilities, io::Error> {
+    let binaries = BINARIES.read().unwrap().clone();
+    run_version(ffprobe(), &binaries.ffprobe)?;
+    let version = run_version(ffmpeg(), &binaries.ffmpeg)?;
+    Ok(Capabilities {
+        version: parse_version(&version),
+        encoders: parse_encoders(&ffmpeg_stdout("-encoders")?),
+        filters: parse_filters(&ffmpeg_stdout("-filters")?),
+        muxers: parse_muxers(&ffmpeg_stdout("-formats")?),
+    })
+}
+
+impl Capabilities {
+    // (major, minor) of release builds, git snapshots like "N-113245-g..." have none
+    pub fn version_number(&self) -> Option<(u32, u32)> {
+        let mut parts = self.version.trim_start_matches('n').split(|c: char| !c.is_ascii_digit());
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next().and_then(|minor| minor.parse().ok()).unwrap_or(0);
+        Some((major, minor))
+    }
+
+    // Snapshots are assumed to be recent enough
+    pub fn is_supported(&self) -> bool {
+        self.version_number().is_none_or(|version| version >= MIN_VERSION)
+    }
+
+    pub fn has_encoder(&self, name: &str) -> bool {
+        self.encoders.contains(name)
+    }
+
+    pub fn has_filter(&self, name: &str) -> bool {
+        self.filters.contains(name)
+    }
+
+    pub fn has_muxer(&self, name: &str) -> bool {
+        self.muxers.contains(name)
+    }
+
+    // Human readable list of everything in `requirements` this build lacks
+    pub fn missing(&self, requirements: &Requirements) -> Vec<String> {
+        let encoders = requirements.encoders.iter().filter(|e| !self.has_encoder(e));
+        let filters = requirements.filters.iter().filter(|f| !self.has_filter(f));
+        let muxers = requirements.muxers.iter().filter(|m| !self.has_muxer(m));
+        encoders
+            .map(|e| format!("encoder {}", e))
+            .chain(filters.map(|f| format!("filter {}", f)))
+            .chain(muxers.map(|m| format!("format {}", m)))
+            .collect()
+    }
+}
+
+// Runs `binary -version`, a missing executable gets a readable error instead of "os error 2"
+fn run_version(mut command: Command, path: &str) -> Result<String, io::Error> {
+    let output = command.arg("-version").output().map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => io::Error::new(io::ErrorKind::NotFound, format!("{} was not found", path)),
+        io::ErrorKind::PermissionDenied => {
+            io::Error::new(io::ErrorKind::PermissionDenied, format!("{} is not executable", path))
+        }
+        _ => e,
+    })?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(io::Error::other(format!("{} -version failed: {}", path, String::from_utf8_lossy(&output.stderr))))
+    }
+}
+
+fn ffmpeg_stdout(option: &str) -> Result<String, io::Error> {
+    let output = ffmpeg().args(["-hide_banner", option]).output()?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(io::Error::other(String::from_utf8_lossy(&output.stderr)))
+    }
+}
+
+// "ffmpeg version 6.1.1-3ubuntu5 Copyright ..." => "6.1.1-3ubuntu5"
+fn parse_version(output: &str) -> String {
+    output
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("ffmpeg version "))
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap_or_default()
+        .to_string()
+}
+
+// A legend, a " ------" separator, then one " V....D name  description" line per encoder.
+fn parse_encoders(output: &str) -> HashSet<String> {
+    output
+        .lines()
+        .skip_while(|line| !line.trim_start().starts_with("---"))
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(String::from)
+        .collect()
+}
+
+// " TSC zoompan  V->V  Apply Zoom & Pan effect." the legend lines have no "->"
+fn parse_filters(output: &str) -> HashSet<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (_, name, io) = (fields.next()?, fields.next()?, fields.next()?);
+            io.contains("->").then(|| name.to_string())
+        })
+        .collect()
+}
+
+// " DE mov,mp4,m4a,3gp,3g2,mj2 QuickTime / MOV", only formats ffmpeg can write count
+fn parse_muxers(output: &str) -> HashSet<String> {
+    output
+        .lines()
+        .skip_while(|line| !line.trim_start().starts_with("--"))
+        .skip(1)
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (flags, names) = (fields.next()?, fields.next()?);
+            flags.contains('E').then_some(names)
+        })
+        .flat_map(|names| names.split(','))
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capabilities(version: &str) -> Capabilities {
+        Capabilities {
+            version: version.to_string(),
+            ..Capabilities::default()
+        }
+    }
+
+    #[test]
+    fn reads_release_and_snapshot_versions() {
+        let ubuntu = "ffmpeg version 6.1.1-3ubuntu5 Copyright (c) 2000-2023 the FFmpeg developers
+built with gcc 13 (Ubuntu 13.2.0-23ubuntu3)
+";
+        assert_eq!(parse_version(ubuntu), "6.1.1-3ubuntu5");
+        let snapshot = "ffmpeg version N-113245-g8e2ebd6b1c-20240118 Copyright (c) 2000-2024 the FFmpeg developers";
+        assert_eq!(parse_version(snapshot), "N-113245-g8e2ebd6b1c-20240118");
+        assert_eq!(parse_version("ffprobe version 7.0.1"), "");
+        assert_eq!(parse_version(""), "");
+    }
+
+    #[test]
+    fn compares_versions_with_the_minimum() {
+        assert_eq!(capabilities("6.1.1-3ubuntu5").version_number(), Some((6, 1)));
+        assert_eq!(capabilities("n7.0.2").version_number(), Some((7, 0)));
+        assert_eq!(capabilities("7").version_number(), Some((7, 0)));
+        assert_eq!(capabilities("N-113245-g8e2ebd6b1c-20240118").version_number(), None);
+        assert!(capabilities("5.1.4").is_supported());
+        assert!(capabilities("N-113245-g8e2ebd6b1c").is_supported());
+        assert!(!capabilities("4.4.2-0ubuntu0.22.04.1").is_supported());
+        assert!(!capabilities("5.0.3").is_supported());
+    }
+
+    #[test]
+    fn reads_encoders_after_the_legend() {
+        let output = "Encoders:
+ V..... = Video
+ A..... = Audio
+ S..... = Subtitle
+ .F.... = Frame-level multithreading
+ ..S... = Slice-level multithreading
+ ...X.. = Codec is experimental
+ ....B. = Supports draw_horiz_band
+ .....D = Supports direct rendering method 1
+ ------
+ V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10 (codec h264)
+ V....D libx265              libx265 H.265 / HEVC (codec hevc)
+ A....D aac                  AAC (Advanced Audio Coding)
+ S..... srt                  SubRip subtitle (codec subrip)
+";
+        let encoders = parse_encoders(output);
+        assert_eq!(encoders.len(), 4);
+        assert!(encoders.contains("libx264") && encoders.contains("aac") && encoders.contains("srt"));
+        assert!(!encoders.contains("Video"));
+    }
+
+    #[test]
+    fn reads_filters_but_not_the_legend() {
+        let output = "Filters:
+  T.. = Timeline support
+  .S. = Slice threading
+  ..C = Command support
+  A = Audio input/output
+  V = Video input/output
+  N = Dynamic number and/or type of input/output
+  | = Source or sink filter
+ ..C atempo            A->A       Adjust audio tempo.
+ TSC xfade             VV->V      Cross fade one video with another video.
+ ... concat            N->N       Concatenate audio and video streams.
+ ... anullsrc          |->A       Null audio source, return empty audio frames.
+ TS. zoompan           V->V       Apply Zoom & Pan effect.
+";
+        let filters = parse_filters(output);
+        assert_eq!(filters.len(), 5);
+        assert!(filters.contains("xfade") && filters.contains("anullsrc") && filters.contains("zoompan"));
+        assert!(!filters.contains("="));
+    }
+
+    #[test]
+    fn reads_muxers_of_ffmpeg_6() {
+        let output = "File formats:
+ D. = Demuxing supported
+ .E = Muxing supported
+ --
+ D  3dostr          3DO STR
+  E 3g2             3GP2 (3GPP2 file format)
+ DE gif             CompuServe Graphics Interchange Format (GIF)
+ D  mov,mp4,m4a,3gp,3g2,mj2 QuickTime / MOV
+  E mp4             MP4 (MPEG-4 Part 14)
+ DE matroska,webm   Matroska / WebM
+";
+        let muxers = parse_muxers(output);
+        for muxer in ["3g2", "gif", "mp4", "matroska", "webm"] {
+            assert!(muxers.contains(muxer), "{} is missing", muxer);
+        }
+        assert!(!muxers.contains("3dostr") && !muxers.contains("mov"));
+    }
+
+    #[test]
+    fn reads_muxers_of_ffmpeg_7_with_device_rows() {
+        let output = "Formats:
+ D.. = Demuxing supported
+ .E. = Muxing supported
+ ..d = Is a device
+ ---
+ D   3dostr          3DO STR
+  E  3g2             3GP2 (3GPP2 file format)
+ DEd alsa            ALSA audio output
+  Ed sdl,sdl2        SDL2 output device
+ D d lavfi           Libavfilter virtual input device
+ D d v4l2            Video4Linux2 device grab
+ DE  matroska,webm   Matroska / WebM
+  E  mp4             MP4 (MPEG-4 Part 14)
+";
+        let muxers = parse_muxers(output);
+        for muxer in ["3g2", "alsa", "sdl", "sdl2", "matroska", "webm", "mp4"] {
+            assert!(muxers.contains(muxer), "{} is missing", muxer);
+        }
+        for format in ["3dostr", "lavfi", "v4l2", "d"] {
+            assert!(!muxers.contains(format), "{} is no muxer", format);
+        }
+    }
+
+    #[test]
+    fn lists_what_the_build_lacks() {
+        let capabilities = Capabilities {
+            encoders: HashSet::from(["libx264".to_string()]),
+            filters: HashSet::from(["scale".to_string()]),
+            ..capabilities("7.0")
+        };
+        let requirements = Requirements {
+            encoders: vec!["libx264", "libwebp_anim"],
+            filters: vec!["scale", "xfade"],
+            muxers: vec!["mp4"],
+        };
+        assert_eq!(capabilities.missing(&requirements), ["encoder libwebp_anim", "filter xfade", "format mp4"]);
+    }
+}
@@ -0,0 +1,449 @@
+use super::audio::AudioAdjust;
+use super::encoding::EncodingSettings;
+use super::jobs::parse_timestamp;
+use super::probe::{probe, Probe};
+use super::{atempo_chain, run_ffmpeg};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Turn {
+    #[default]
+    Clockwise,
+    CounterClockwise,
+    UpsideDown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Position {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+    Center,
+}
+
+// One stage of a chain, all of them end up in a single filter graph.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    Trim { start: Option<String>, end: Option<String> },
+    Crop { width: u32, height: u32, x: u32, y: u32 },
+    Rotate(Turn),
+    Resize { width: i32, height: i32 }, // -2 keeps the aspect ratio
+    Watermark { image: String, position: Position, margin: u32, opacity: f64 },
+    Text { text: String, size: u32, position: Position },
+    Speed(f64),
+    Audio(AudioAdjust),
+}
+
+impl Step {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Step::Trim { .. } => "Trim",
+            Step::Crop { .. } => "Crop",
+            Step::Rotate(_) => "Rotate",
+            Step::Resize { .. } => "Resize",
+            Step::Watermark { .. } => "Watermark",
+            Step::Text { .. } => "Text",
+            Step::Speed(_) => "Speed",
+            Step::Audio(_) => "Audio adjust",
+        }
+    }
+
+    fn is_video(&self) -> bool {
+        !matches!(self, Step::Trim { .. } | Step::Audio(_))
+    }
+}
+
+// Rejects chains that can't be expressed as one encode of `input`.
+pub fn validate(steps: &[Step], has_video: bool, has_audio: bool) -> Result<(), io::Error> {
+    let invalid = |message: String| Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    if steps.is_empty() {
+        return invalid("Add at least one step".to_string());
+    }
+    for (i, step) in steps.iter().enumerate() {
+        if matches!(step, Step::Trim { .. } | Step::Speed(_)) && steps[..i].iter().any(|s| s.name() == step.name()) {
+            return invalid(format!("{} can only be used once", step.name()));
+        }
+        match step {
+            // seeking happens on the input, before any filter
+            Step::Trim { .. } if i > 0 => return invalid("Trim has to be the first step".to_string()),
+            Step::Trim { start: None, end: None } => return invalid("Trim needs a start or an end".to_string()),
+            Step::Crop { width, height, .. } if *width == 0 || *height == 0 => {
+                return invalid("Crop size can't be zero".to_string())
+            }
+            Step::Resize { width, height } if *width <= 0 && *height <= 0 => {
+                return invalid("Resize needs a width or a height".to_string())
+            }
+            Step::Watermark { image, .. } if !Path::new(image).is_file() => {
+                return invalid(format!("Watermark image not found: {}", image))
+            }
+            Step::Watermark { opacity, .. } if !(0.0..=1.0).contains(opacity) => {
+                return invalid("Watermark opacity must be between 0 and 1".to_string())
+            }
+            Step::Text { text, .. } if text.trim().is_empty() => return invalid("Text step has no text".to_string()),
+            Step::Speed(factor) if !factor.is_finite() || *factor <= 0.0 => {
+                return invalid(format!("Invalid speed factor: {}", factor))
+            }
+            // -itsoffset needs a second input, which the graph can't combine with the other filters
+            Step::Audio(adjust) if adjust.offset < 0.0 => {
+                return invalid("A negative audio offset can't be chained, use Adjust audio instead".to_string())
+            }
+            Step::Audio(_) if !has_audio => return invalid("The input has no audio to adjust".to_string()),
+            step if step.is_video() && !has_video => {
+                return invalid(format!("{} needs a video stream", step.name()))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Full ffmpeg argument list for the chain, without the binary itself.
+pub fn chain_args(
+    input: &str,
+    output_file: &str,
+    steps: &[Step],
+    encoding: &EncodingSettings,
+) -> Result<Vec<String>, io::Error> {
+    probed_chain_args(input, output_file, steps, &probe(input)?, encoding)
+}
+
+// chain_args once the streams and the duration of `input` are known
+fn probed_chain_args(
+    input: &str,
+    output_file: &str,
+    steps: &[Step],
+    probe: &Probe,
+    encoding: &EncodingSettings,
+) -> Result<Vec<String>, io::Error> {
+    let has_video = !probe.streams_of("video").is_empty();
+    let has_audio = !probe.audio_streams().is_empty();
+    validate(steps, has_video, has_audio)?;
+
+    let mut args: Vec<String> = vec!["-hide_banner".into()];
+    // running length of the output, the audio fade out is placed against it
+    let mut duration = probe.duration();
+    if let Some(Step::Trim { start, end }) = steps.first() {
+        let start_seconds = start.as_deref().and_then(parse_timestamp).unwrap_or(0.0);
+        if let Some(start) = start {
+            args.extend(["-ss".into(), start.clone()]);
+        }
+        if let Some(end) = end {
+            args.extend(["-to".into(), end.clone()]);
+        }
+        let end_seconds = end.as_deref().and_then(parse_timestamp).or(duration);
+        duration = end_seconds.map(|end| (end - start_seconds).max(0.0));
+    }
+    args.extend(["-i".into(), input.into()]);
+
+    let mut graph: Vec<String> = vec![];
+    let mut video_filters: Vec<String> = vec![];
+    let mut audio_filters: Vec<String> = vec![];
+    let mut video_label = "0:v".to_string();
+    let mut inputs = 1;
+    for step in steps {
+        match step {
+            Step::Trim { .. } => {}
+            Step::Crop { width, height, x, y } => video_filters.push(format!("crop={}:{}:{}:{}", width, height, x, y)),
+            Step::Rotate(turn) => video_filters.push(turn.filter().to_string()),
+            Step::Resize { width, height } => video_filters.push(format!("scale={}:{}", width, height)),
+            Step::Text { text, size, position } => video_filters.push(format!(
+                "drawtext=text='{}':fontsize={}:fontcolor=white:box=1:boxcolor=black@0.5:boxborderw=8:{}",
+                escape_text(text),
+                size,
+                position.placement("w-tw", "h-th", 20)
+            )),
+            Step::Watermark { image, position, margin, opacity } => {
+                // the overlay needs the image as a second input, so the chain so far is closed with a label
+                args.extend(["-i".into(), image.clone()]);
+                let base = format!("v{}", inputs);
+                let filters = if video_filters.is_empty() { "null".to_string() } else { video_filters.join(",") };
+                graph.push(format!("[{}]{}[{}]", video_label, filters, base));
+                graph.push(format!("[{}:v]format=rgba,colorchannelmixer=aa={}[wm{}]", inputs, opacity, inputs));
+                video_label = format!("o{}", inputs);
+                graph.push(format!(
+                    "[{}][wm{}]overlay={}[{}]",
+                    base,
+                    inputs,
+                    position.placement("W-w", "H-h", *margin),
+                    video_label
+                ));
+                video_filters.clear();
+                inputs += 1;
+            }
+            Step::Speed(factor) => {
+                video_filters.push(format!("setpts=PTS/{}", factor));
+                if has_audio {
+                    audio_filters.push(atempo_chain(*factor));
+                }
+                duration = duration.map(|d| d / factor);
+            }
+            Step::Audio(adjust) => {
+                if adjust.offset > 0.0 {
+                    audio_filters.push(format!("adelay={}:all=1", (adjust.offset * 1000.0).round() as u64));
+                }
+                if adjust.gain_db != 0.0 {
+                    audio_filters.push(format!("volume={}dB", adjust.gain_db));
+                }
+                if adjust.fade_in > 0.0 {
+                    audio_filters.push(format!("afade=t=in:st=0:d={}", adjust.fade_in));
+                }
+                if adjust.fade_out > 0.0 {
+                    let duration = duration.ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidData, "Unable to read the input duration")
+                    })?;
+                    let start = (duration - adjust.fade_out).max(0.0);
+                    audio_filters.push(format!("afade=t=out:st={}:d={}", start, adjust.fade_out));
+                }
+            }
+        }
+    }
+
+    let video_filtered = video_label != "0:v" || !video_filters.is_empty();
+    if video_filtered {
+        let filters = if video_filters.is_empty() { "null".to_string() } else { video_filters.join(",") };
+        graph.push(format!("[{}]{}[vout]", video_label, filters));
+    }
+    let audio_filtered = !audio_filters.is_empty();
+    if audio_filtered {
+        graph.push(format!("[0:a]{}[aout]", audio_filters.join(",")));
+    }
+    if !graph.is_empty() {
+        args.extend(["-filter_complex".into(), graph.join(";")]);
+    }
+    args.extend(["-map".into(), if video_filtered { "[vout]".into() } else { "0:v?".into() }]);
+    args.extend(["-map".into(), if audio_filtered { "[aout]".into() } else { "0:a?".into() }]);
+    args.extend(encoding.video_args(video_filtered)?);
+    args.extend(encoding.audio_args(audio_filtered)?);
+    args.push(output_file.into());
+    args.push("-n".into());
+    Ok(args)
+}
+
+// Applies every step in order with a single decode and encode.
+pub fn run_chain(input: &str, output_file: &str, steps: &[Step], encoding: &EncodingSettings) -> Result<(), io::Error> {
+    run_ffmpeg(&chain_args(input, output_file, steps, encoding)?)
+}
+
+// drawtext text goes through three parsers: the text expansion (\ and %), the option
+// list (\, : and ') and the graph, where the surrounding quotes protect the rest and
+// a quote is closed, escaped and reopened: '\''
+fn escape_text(text: &str) -> String {
+    let expanded = text.replace('\\', "\\\\").replace('%', "\\%");
+    let option = expanded.replace('\\', "\\\\").replace(':', "\\:").replace('\'', "\\'");
+    option.replace('\'', "'\\''")
+}
+
+impl Turn {
+    fn filter(&self) -> &'static str {
+        match self {
+            Turn::Clockwise => "transpose=clock",
+            Turn::CounterClockwise => "transpose=cclock",
+            Turn::UpsideDown => "hflip,vflip",
+        }
+    }
+}
+
+impl Position {
+    pub const ALL: [Position; 5] = [
+        Position::TopLeft,
+        Position::TopRight,
+        Position::BottomLeft,
+        Position::BottomRight,
+        Position::Center,
+    ];
+
+    // x/y expressions, `right` and `bottom` are the filter's free space, e.g. W-w for overlay
+    fn placement(&self, right: &str, bottom: &str, margin: u32) -> String {
+        let (x, y) = match self {
+            Position::TopLeft => (margin.to_string(), margin.to_string()),
+            Position::TopRight => (format!("{}-{}", right, margin), margin.to_string()),
+            Position::BottomLeft => (margin.to_string(), format!("{}-{}", bottom, margin)),
+            Position::BottomRight => (format!("{}-{}", right, margin), format!("{}-{}", bottom, margin)),
+            Position::Center => (format!("({})/2", right), format!("({})/2", bottom)),
+        };
+        format!("x={}:y={}", x, y)
+    }
+}
+
+impl fmt::Display for Turn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Turn::Clockwise => "90 Clockwise",
+            Turn::CounterClockwise => "90 Counterclockwise",
+            Turn::UpsideDown => "180 degrees",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Position::TopLeft => "Top left",
+            Position::TopRight => "Top right",
+            Position::BottomLeft => "Bottom left",
+            Position::BottomRight => "Bottom right",
+            Position::Center => "Center",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::probe::Stream;
+
+    // One level of ffmpeg's av_get_token: quotes keep everything up to the next
+    // quote, a backslash outside of them keeps the next character. The text
+    // expansion only knows the backslash.
+    fn unescape(value: &str, quotes: bool) -> String {
+        let (mut result, mut chars) = (String::new(), value.chars());
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => result.extend(chars.next()),
+                '\'' if quotes => result.extend(chars.by_ref().take_while(|c| *c != '\'')),
+                c => result.push(c),
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn escapes_drawtext_text_for_every_parser() {
+        let text = r"it's 100%: C:\path, [done]; ok";
+        let escaped = escape_text(text);
+        assert_eq!(escaped, r"it\'\''s 100\\%\: C\:\\\\path, [done]; ok");
+        // the graph, the option list, then the text expansion
+        let graph = unescape(&format!("'{}'", escaped), true);
+        assert_eq!(unescape(&unescape(&graph, true), false), text);
+    }
+
+    fn error(steps: &[Step], has_video: bool, has_audio: bool) -> String {
+        validate(steps, has_video, has_audio).unwrap_err().to_string()
+    }
+
+    fn trim(start: &str, end: &str) -> Step {
+        Step::Trim { start: Some(start.to_string()), end: Some(end.to_string()) }
+    }
+
+    const CROP: Step = Step::Crop { width: 100, height: 100, x: 0, y: 0 };
+
+    fn audio(adjust: AudioAdjust) -> Step {
+        Step::Audio(adjust)
+    }
+
+    #[test]
+    fn validate_keeps_trim_first_and_steps_unique() {
+        assert!(validate(&[trim("1", "2"), CROP, Step::Speed(2.0)], true, true).is_ok());
+        assert_eq!(error(&[], true, true), "Add at least one step");
+        assert_eq!(error(&[CROP, trim("1", "2")], true, true), "Trim has to be the first step");
+        assert_eq!(error(&[trim("1", "2"), trim("3", "4")], true, true), "Trim can only be used once");
+        assert_eq!(error(&[Step::Speed(2.0), CROP, Step::Speed(0.5)], true, true), "Speed can only be used once");
+        assert_eq!(error(&[Step::Trim { start: None, end: None }], true, true), "Trim needs a start or an end");
+        // the other steps can repeat
+        assert!(validate(&[CROP, Step::Rotate(Turn::Clockwise), CROP], true, false).is_ok());
+    }
+
+    #[test]
+    fn validate_refuses_negative_offsets_and_bad_values() {
+        let late = audio(AudioAdjust { offset: 0.5, ..AudioAdjust::default() });
+        let early = audio(AudioAdjust { offset: -0.5, ..AudioAdjust::default() });
+        assert!(validate(&[late], true, true).is_ok());
+        assert_eq!(error(&[early], true, true), "A negative audio offset can't be chained, use Adjust audio instead");
+        assert_eq!(error(&[Step::Speed(0.0)], true, true), "Invalid speed factor: 0");
+        assert_eq!(error(&[Step::Resize { width: -2, height: -2 }], true, true), "Resize needs a width or a height");
+        let image = "/no/such/logo.png".to_string();
+        let missing = Step::Watermark { image, position: Position::Center, margin: 0, opacity: 1.0 };
+        assert_eq!(error(&[missing], true, true), "Watermark image not found: /no/such/logo.png");
+    }
+
+    #[test]
+    fn validate_needs_the_streams_the_steps_change() {
+        let text = Step::Text { text: "hello".to_string(), size: 24, position: Position::TopLeft };
+        assert_eq!(error(&[CROP], false, true), "Crop needs a video stream");
+        assert_eq!(error(&[text], false, true), "Text needs a video stream");
+        let louder = audio(AudioAdjust { gain_db: 3.0, ..AudioAdjust::default() });
+        assert_eq!(error(&[louder], true, false), "The input has no audio to adjust");
+        // trimming and adjusting the audio of an audio file is fine
+        assert!(validate(&[trim("1", "2"), audio(AudioAdjust::default())], false, true).is_ok());
+    }
+
+    fn probe(video: bool, audio: bool) -> Probe {
+        let stream = |index: usize, kind: &str| Stream {
+            index,
+            codec_type: kind.to_string(),
+            ..Stream::default()
+        };
+        let mut probe = Probe::default();
+        if video {
+            probe.streams.push(stream(probe.streams.len(), "video"));
+        }
+        if audio {
+            probe.streams.push(stream(probe.streams.len(), "audio"));
+        }
+        probe.format.duration = Some("10.0".to_string());
+        probe
+    }
+
+    fn graph(args: &[String]) -> &str {
+        let at = args.iter().position(|arg| arg == "-filter_complex").expect("no filter graph");
+        &args[at + 1]
+    }
+
+    fn watermark(name: &str) -> String {
+        let image = std::env::temp_dir().join(format!("owl-chain-test-{}.png", name));
+        std::fs::write(&image, b"png").unwrap();
+        image.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn labels_the_graph_around_a_watermark_in_the_middle() {
+        let image = watermark("middle");
+        let steps = [
+            CROP,
+            Step::Watermark { image: image.clone(), position: Position::BottomRight, margin: 10, opacity: 0.5 },
+            Step::Resize { width: 640, height: -2 },
+        ];
+        let args = probed_chain_args("in.mp4", "out.mp4", &steps, &probe(true, true), &EncodingSettings::default()).unwrap();
+        assert_eq!(&args[..5], ["-hide_banner", "-i", "in.mp4", "-i", image.as_str()]);
+        assert_eq!(
+            graph(&args),
+            "[0:v]crop=100:100:0:0[v1];[1:v]format=rgba,colorchannelmixer=aa=0.5[wm1];\
+             [v1][wm1]overlay=x=W-w-10:y=H-h-10[o1];[o1]scale=640:-2[vout]"
+        );
+        assert_eq!(&args[7..], ["-map", "[vout]", "-map", "0:a?", "out.mp4", "-n"]);
+    }
+
+    #[test]
+    fn numbers_the_inputs_of_consecutive_watermarks() {
+        let image = watermark("twice");
+        let mark = |position| Step::Watermark { image: image.clone(), position, margin: 0, opacity: 1.0 };
+        let steps = [mark(Position::TopLeft), mark(Position::Center)];
+        let args = probed_chain_args("in.mp4", "out.mp4", &steps, &probe(true, false), &EncodingSettings::default()).unwrap();
+        assert_eq!(args.iter().filter(|arg| *arg == "-i").count(), 3);
+        assert_eq!(
+            graph(&args),
+            "[0:v]null[v1];[1:v]format=rgba,colorchannelmixer=aa=1[wm1];[v1][wm1]overlay=x=0:y=0[o1];\
+             [o1]null[v2];[2:v]format=rgba,colorchannelmixer=aa=1[wm2];[v2][wm2]overlay=x=(W-w)/2:y=(H-h)/2[o2];\
+             [o2]null[vout]"
+        );
+    }
+
+    #[test]
+    fn places_the_fade_out_against_the_trimmed_and_sped_up_length() {
+        let steps = [
+            trim("00:00:02", "00:00:08"),
+            Step::Speed(2.0),
+            audio(AudioAdjust { fade_out: 1.0, ..AudioAdjust::default() }),
+        ];
+        let args = probed_chain_args("in.mp4", "out.mp4", &steps, &probe(true, true), &EncodingSettings::default()).unwrap();
+        assert_eq!(&args[..7], ["-hide_banner", "-ss", "00:00:02", "-to", "00:00:08", "-i", "in.mp4"]);
+        assert_eq!(graph(&args), "[0:v]setpts=PTS/2[vout];[0:a]atempo=2,afade=t=out:st=2:d=1[aout]");
+    }
+}
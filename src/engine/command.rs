@@ -0,0 +1,187 @@
+use super::BINARIES;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+// What happens to the ffmpeg calls made on the current thread.
+enum Plan {
+    // collect them without running anything, for the command preview
+    Record(Vec<Vec<String>>),
+    // run these edited command lines instead, one per call
+    Replace(VecDeque<Vec<String>>),
+}
+
+thread_local! {
+    static PLAN: RefCell<Option<Plan>> = const { RefCell::new(None) };
+    // (written, shown) paths, commands show the final output instead of its temp file
+    static ALIASES: RefCell<Vec<(String, String)>> = const { RefCell::new(vec![]) };
+}
+
+// Runs `work` without starting ffmpeg and returns the command lines it would
+// have run. Steps that need ffmpeg's output only see an empty one.
+pub fn record<T>(work: impl FnOnce() -> T) -> (Vec<Vec<String>>, T) {
+    PLAN.with(|plan| *plan.borrow_mut() = Some(Plan::Record(vec![])));
+    let result = work();
+    let commands = PLAN.with(|plan| match plan.borrow_mut().take() {
+        Some(Plan::Record(commands)) => commands,
+        _ => vec![],
+    });
+    (commands, result)
+}
+
+pub(super) fn recording() -> bool {
+    PLAN.with(|plan| matches!(plan.borrow().as_ref(), Some(Plan::Record(_))))
+}
+
+// Runs `work` with its ffmpeg calls replaced by `commands`, in order. Calls
+// beyond the edited lines run as generated.
+pub fn with_commands<T>(commands: Vec<Vec<String>>, work: impl FnOnce() -> T) -> T {
+    PLAN.with(|plan| *plan.borrow_mut() = Some(Plan::Replace(commands.into())));
+    let result = work();
+    PLAN.with(|plan| *plan.borrow_mut() = None);
+    result
+}
+
+// Runs `work` with the calls writing to `written` recorded as writing to `shown`,
+// edited lines naming `shown` write to `written` again.
+pub(super) fn shown_as<T>(written: &str, shown: &str, work: impl FnOnce() -> T) -> T {
+    ALIASES.with(|aliases| aliases.borrow_mut().push((written.to_string(), shown.to_string())));
+    let result = work();
+    ALIASES.with(|aliases| aliases.borrow_mut().pop());
+    result
+}
+
+fn replace_paths(command: Vec<String>, to_shown: bool) -> Vec<String> {
+    ALIASES.with(|aliases| {
+        let aliases = aliases.borrow();
+        command
+            .into_iter()
+            .map(|arg| {
+                let alias = aliases.iter().find_map(|(written, shown)| {
+                    let (from, to) = if to_shown { (written, shown) } else { (shown, written) };
+                    (arg == *from).then(|| to.clone())
+                });
+                alias.unwrap_or(arg)
+            })
+            .collect()
+    })
+}
+
+// The command line to run for `args`, program first, or None when recording.
+pub(super) fn planned(args: &[String]) -> Option<Vec<String>> {
+    let mut command = vec![BINARIES.read().unwrap().ffmpeg.clone()];
+    command.extend(args.iter().cloned());
+    PLAN.with(|plan| match plan.borrow_mut().as_mut() {
+        Some(Plan::Record(commands)) => {
+            commands.push(replace_paths(command, true));
+            None
+        }
+        Some(Plan::Replace(commands)) => Some(commands.pop_front().map_or(command, |edited| replace_paths(edited, false))),
+        None => Some(command),
+    })
+}
+
+// POSIX shell quoting, plain words stay bare: ffmpeg -i 'my video.mp4'
+pub fn quote(command: &[String]) -> String {
+    command
+        .iter()
+        .map(|arg| {
+            let plain = !arg.is_empty()
+                && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_+=/.,:@%".contains(c));
+            if plain {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Splits a command line the way a POSIX shell would, without expansions.
+pub fn split(line: &str) -> Result<Vec<String>, String> {
+    let mut args = vec![];
+    let mut current: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(arg) = current.take() {
+                    args.push(arg);
+                }
+            }
+            '\'' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err("Unterminated ' quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) if "\"\\$`".contains(c) => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err("Unterminated \" quote".to_string()),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err("Unterminated \" quote".to_string()),
+                    }
+                }
+            }
+            '\\' => {
+                let escaped = chars.next().ok_or("Trailing \\")?;
+                current.get_or_insert_with(String::new).push(escaped);
+            }
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(arg) = current {
+        args.push(arg);
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn quotes_only_what_the_shell_would_split() {
+        let command = args(&["ffmpeg", "-i", "my video.mp4", "-vf", "scale=640:-1", "it's.mp4", ""]);
+        assert_eq!(quote(&command), r#"ffmpeg -i 'my video.mp4' -vf scale=640:-1 'it'\''s.mp4' ''"#);
+    }
+
+    #[test]
+    fn splits_quoted_and_escaped_arguments() {
+        let line = r#"ffmpeg -i "my \"best\" video.mp4" -af 'volume=2,atempo=2' out\ file.mp4 "a\b" '' x"#;
+        let expected = args(&["ffmpeg", "-i", "my \"best\" video.mp4", "-af", "volume=2,atempo=2", "out file.mp4", "a\\b", "", "x"]);
+        assert_eq!(split(line), Ok(expected));
+        assert_eq!(split("  -y \n -n "), Ok(args(&["-y", "-n"])));
+    }
+
+    #[test]
+    fn split_reads_back_quoted_commands() {
+        let command = args(&["ffmpeg", "-vf", "drawtext=text='it\\'s':fontsize=24", "$HOME/out (2).mp4", "tab\there", ""]);
+        assert_eq!(split(&quote(&command)), Ok(command));
+    }
+
+    #[test]
+    fn split_rejects_unfinished_quotes() {
+        assert_eq!(split("ffmpeg -i 'in.mp4"), Err("Unterminated ' quote".to_string()));
+        assert_eq!(split("ffmpeg -i \"in.mp4"), Err("Unterminated \" quote".to_string()));
+        assert_eq!(split("ffmpeg -i in.mp4 \\"), Err("Trailing \\".to_string()));
+    }
+}
@@ -0,0 +1,273 @@
+use super::encoding::EncodingSettings;
+use super::probe::{Probe, Stream};
+use super::run_ffmpeg;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum ContainerFormat {
+    #[default]
+    Mp4,
+    Mkv,
+    Mov,
+    WebM,
+    Ts,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamAction {
+    Copy,
+    Encode(&'static str),
+    Drop,
+}
+
+// Text subtitles can be converted between each other, bitmap ones (PGS, VobSub) can't.
+const TEXT_SUBTITLES: [&str; 5] = ["subrip", "ass", "ssa", "webvtt", "mov_text"];
+
+impl ContainerFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ContainerFormat::Mp4 => "mp4",
+            ContainerFormat::Mkv => "mkv",
+            ContainerFormat::Mov => "mov",
+            ContainerFormat::WebM => "webm",
+            ContainerFormat::Ts => "ts",
+        }
+    }
+
+    // The container written to `path`, from its extension
+    pub fn of_file(path: &str) -> Option<ContainerFormat> {
+        let extension = Path::new(path).extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "mp4" | "m4v" | "m4a" => Some(ContainerFormat::Mp4),
+            "mkv" | "mka" | "mks" => Some(ContainerFormat::Mkv),
+            "mov" => Some(ContainerFormat::Mov),
+            "webm" => Some(ContainerFormat::WebM),
+            "ts" | "m2ts" => Some(ContainerFormat::Ts),
+            _ => None,
+        }
+    }
+
+    // Name of the ffmpeg muxer writing this container
+    pub fn muxer(&self) -> &'static str {
+        match self {
+            ContainerFormat::Mp4 => "mp4",
+            ContainerFormat::Mkv => "matroska",
+            ContainerFormat::Mov => "mov",
+            ContainerFormat::WebM => "webm",
+            ContainerFormat::Ts => "mpegts",
+        }
+    }
+
+    // Codec/container compatibility table, Matroska takes everything but the
+    // mp4 only mov_text subtitles.
+    pub fn supports(&self, kind: &str, codec: &str) -> bool {
+        let codecs: &[&str] = match (self, kind) {
+            (ContainerFormat::Mkv, "subtitle") => return codec != "mov_text",
+            (ContainerFormat::Mkv, _) => return true,
+            (ContainerFormat::Mp4, "video") => &["h264", "hevc", "av1", "vp9", "mpeg4", "mpeg2video"],
+            (ContainerFormat::Mp4, "audio") => &["aac", "mp3", "ac3", "eac3", "opus", "flac", "alac"],
+            (ContainerFormat::Mp4, "subtitle") => &["mov_text"],
+            (ContainerFormat::Mov, "video") => &["h264", "hevc", "mpeg4", "prores", "mjpeg"],
+            (ContainerFormat::Mov, "audio") => &["aac", "mp3", "ac3", "alac", "pcm_s16le", "pcm_s24le"],
+            (ContainerFormat::Mov, "subtitle") => &["mov_text"],
+            (ContainerFormat::WebM, "video") => &["vp8", "vp9", "av1"],
+            (ContainerFormat::WebM, "audio") => &["vorbis", "opus"],
+            (ContainerFormat::WebM, "subtitle") => &["webvtt"],
+            (ContainerFormat::Ts, "video") => &["h264", "hevc", "mpeg2video", "mpeg4"],
+            (ContainerFormat::Ts, "audio") => &["aac", "mp3", "mp2", "ac3", "eac3"],
+            (ContainerFormat::Ts, "subtitle") => &["dvb_subtitle"],
+            _ => &[],
+        };
+        codecs.contains(&codec)
+    }
+
+    // Encoder used for streams the container can't hold as they are
+    pub fn encoder(&self, kind: &str, codec: &str) -> Option<&'static str> {
+        match (self, kind) {
+            (ContainerFormat::WebM, "video") => Some("libvpx-vp9"),
+            (ContainerFormat::WebM, "audio") => Some("libopus"),
+            (_, "video") => Some("libx264"),
+            (_, "audio") => Some("aac"),
+            (ContainerFormat::Mp4 | ContainerFormat::Mov, "subtitle") if TEXT_SUBTITLES.contains(&codec) => Some("mov_text"),
+            (ContainerFormat::WebM, "subtitle") if TEXT_SUBTITLES.contains(&codec) => Some("webvtt"),
+            (ContainerFormat::Mkv, "subtitle") if TEXT_SUBTITLES.contains(&codec) => Some("srt"),
+            _ => None,
+        }
+    }
+
+    // Cover art travels as a picture, mp4 and mov only take JPEG and PNG ones
+    fn holds_cover_art(&self, codec: &str) -> bool {
+        match self {
+            ContainerFormat::Mkv => true,
+            ContainerFormat::Mp4 | ContainerFormat::Mov => matches!(codec, "mjpeg" | "png"),
+            ContainerFormat::WebM | ContainerFormat::Ts => false,
+        }
+    }
+
+    pub fn action_for(&self, stream: &Stream) -> StreamAction {
+        let kind = stream.codec_type.as_str();
+        if !matches!(kind, "video" | "audio" | "subtitle") {
+            return StreamAction::Drop;
+        }
+        // encoding it would turn the cover into a one frame video track
+        if stream.disposition.get("attached_pic").is_some_and(|&flag| flag == 1) {
+            return if self.holds_cover_art(&stream.codec_name) { StreamAction::Copy } else { StreamAction::Drop };
+        }
+        if self.supports(kind, &stream.codec_name) {
+            return StreamAction::Copy;
+        }
+        match self.encoder(kind, &stream.codec_name) {
+            Some(encoder) => StreamAction::Encode(encoder),
+            None => StreamAction::Drop,
+        }
+    }
+}
+
+// Remuxes into `container`, only the streams it can't hold are re-encoded. The
+// chosen encoders replace the fallbacks when the container accepts their output.
+pub fn convert(
+    input: &str,
+    output_file: &str,
+    probe: &Probe,
+    container: ContainerFormat,
+    encoding: &EncodingSettings,
+) -> Result<(), io::Error> {
+    let mut args: Vec<String> = vec!["-hide_banner".into(), "-i".into(), input.into()];
+    let mut output_index = 0;
+    for stream in &probe.streams {
+        let Some(codec) = stream_codec(container, stream, encoding) else {
+            continue;
+        };
+        args.push("-map".into());
+        args.push(format!("0:{}", stream.index));
+        args.push(format!("-c:{}", output_index));
+        args.push(codec.into());
+        // the encoder options only fit the encoder they were picked for, copies take none
+        let index = output_index.to_string();
+        match stream.codec_type.as_str() {
+            _ if codec == "copy" => {}
+            "video" if encoding.video_codec.encoder() == Some(codec) => args.extend(encoding.video_options(&index)),
+            "audio" if encoding.audio_codec.encoder() == Some(codec) => args.extend(encoding.audio_options(&index)),
+            _ => {}
+        }
+        output_index += 1;
+    }
+    if output_index == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "The input has no streams to convert"));
+    }
+    if matches!(container, ContainerFormat::Mp4 | ContainerFormat::Mov) {
+        args.push("-movflags".into());
+        args.push("+faststart".into());
+    }
+    args.push(output_file.into());
+    args.push("-n".into());
+
+    run_ffmpeg(&args)
+}
+
+// Codec option for `stream` in the converted file, "copy" or an encoder, None drops it
+pub fn stream_codec(container: ContainerFormat, stream: &Stream, encoding: &EncodingSettings) -> Option<&'static str> {
+    match container.action_for(stream) {
+        StreamAction::Drop => None,
+        StreamAction::Copy => Some("copy"),
+        StreamAction::Encode(encoder) => Some(chosen_encoder(container, &stream.codec_type, encoding).unwrap_or(encoder)),
+    }
+}
+
+// The encoder picked in the settings, if the container can hold what it produces
+fn chosen_encoder(container: ContainerFormat, kind: &str, encoding: &EncodingSettings) -> Option<&'static str> {
+    let (codec, encoder) = match kind {
+        "video" => (encoding.video_codec.codec_name()?, encoding.video_codec.encoder()?),
+        "audio" => (encoding.audio_codec.codec_name()?, encoding.audio_codec.encoder()?),
+        _ => return None,
+    };
+    container.supports(kind, codec).then_some(encoder)
+}
+
+impl fmt::Display for ContainerFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            ContainerFormat::Mp4 => "MP4",
+            ContainerFormat::Mkv => "Matroska (mkv)",
+            ContainerFormat::Mov => "QuickTime (mov)",
+            ContainerFormat::WebM => "WebM",
+            ContainerFormat::Ts => "MPEG-TS",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl fmt::Display for StreamAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StreamAction::Copy => write!(f, "copy"),
+            StreamAction::Encode(encoder) => write!(f, "re-encode with {}", encoder),
+            StreamAction::Drop => write!(f, "drop (not supported)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(kind: &str, codec: &str) -> Stream {
+        Stream {
+            codec_type: kind.to_string(),
+            codec_name: codec.to_string(),
+            ..Stream::default()
+        }
+    }
+
+    fn cover(codec: &str) -> Stream {
+        let mut cover = stream("video", codec);
+        cover.disposition.insert("attached_pic".to_string(), 1);
+        cover
+    }
+
+    #[test]
+    fn picks_an_action_per_stream() {
+        let cases = [
+            (ContainerFormat::Mp4, stream("video", "h264"), StreamAction::Copy),
+            (ContainerFormat::Mp4, stream("audio", "vorbis"), StreamAction::Encode("aac")),
+            (ContainerFormat::Mp4, stream("subtitle", "subrip"), StreamAction::Encode("mov_text")),
+            (ContainerFormat::Mp4, stream("subtitle", "hdmv_pgs_subtitle"), StreamAction::Drop),
+            (ContainerFormat::Mkv, stream("video", "prores"), StreamAction::Copy),
+            (ContainerFormat::Mkv, stream("subtitle", "ass"), StreamAction::Copy),
+            (ContainerFormat::Mkv, stream("subtitle", "mov_text"), StreamAction::Encode("srt")),
+            (ContainerFormat::WebM, stream("video", "h264"), StreamAction::Encode("libvpx-vp9")),
+            (ContainerFormat::WebM, stream("audio", "aac"), StreamAction::Encode("libopus")),
+            (ContainerFormat::WebM, stream("video", "vp9"), StreamAction::Copy),
+            (ContainerFormat::Ts, stream("subtitle", "subrip"), StreamAction::Drop),
+            (ContainerFormat::Mkv, stream("data", "bin_data"), StreamAction::Drop),
+            (ContainerFormat::Mkv, stream("attachment", "ttf"), StreamAction::Drop),
+        ];
+        for (container, stream, action) in cases {
+            assert_eq!(container.action_for(&stream), action, "{} {} into {}", stream.codec_type, stream.codec_name, container);
+        }
+    }
+
+    #[test]
+    fn copies_or_drops_cover_art() {
+        assert_eq!(ContainerFormat::Mp4.action_for(&cover("mjpeg")), StreamAction::Copy);
+        assert_eq!(ContainerFormat::Mov.action_for(&cover("png")), StreamAction::Copy);
+        assert_eq!(ContainerFormat::Mkv.action_for(&cover("bmp")), StreamAction::Copy);
+        // re-encoding a bmp cover would make it a one frame video
+        assert_eq!(ContainerFormat::Mp4.action_for(&cover("bmp")), StreamAction::Drop);
+        assert_eq!(ContainerFormat::WebM.action_for(&cover("mjpeg")), StreamAction::Drop);
+    }
+
+    #[test]
+    fn knows_what_each_container_holds() {
+        assert!(!ContainerFormat::WebM.supports("video", "h264"));
+        assert!(!ContainerFormat::WebM.supports("audio", "aac"));
+        assert!(ContainerFormat::WebM.supports("audio", "opus"));
+        assert!(!ContainerFormat::Mkv.supports("subtitle", "mov_text"));
+        assert!(ContainerFormat::Mkv.supports("audio", "pcm_s24le"));
+        assert!(ContainerFormat::Mp4.supports("subtitle", "mov_text"));
+        assert!(!ContainerFormat::Mp4.supports("subtitle", "subrip"));
+    }
+}
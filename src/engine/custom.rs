@@ -0,0 +1,48 @@
+use super::encoding::EncodingSettings;
+use super::run_ffmpeg;
+use std::io;
+
+// Options typed by the user around the input and output the app manages.
+#[derive(Debug, Clone, Default)]
+pub struct CustomArgs {
+    pub input_options: Vec<String>,  // before -i
+    pub output_options: Vec<String>, // before the output file
+    pub filter_complex: Option<String>,
+}
+
+pub fn custom_args(
+    input: &str,
+    output_file: &str,
+    custom: &CustomArgs,
+    encoding: &EncodingSettings,
+) -> Result<Vec<String>, io::Error> {
+    // the app owns the files and the overwrite decision
+    let reserved = ["-i", "-y", "-n"];
+    let all_options = custom.input_options.iter().chain(&custom.output_options);
+    if let Some(option) = all_options.into_iter().find(|arg| reserved.contains(&arg.as_str())) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is set by the app, remove it from the options", option),
+        ));
+    }
+
+    let mut args: Vec<String> = vec!["-hide_banner".into()];
+    args.extend(custom.input_options.iter().cloned());
+    args.push("-i".into());
+    args.push(input.into());
+    if let Some(graph) = &custom.filter_complex {
+        args.extend(["-filter_complex".into(), graph.clone()]);
+    }
+    // the codec panel goes first so the typed options win over it
+    let filtered = custom.filter_complex.is_some();
+    args.extend(encoding.video_args(filtered)?);
+    args.extend(encoding.audio_args(filtered)?);
+    args.extend(custom.output_options.iter().cloned());
+    args.push(output_file.into());
+    args.push("-n".into());
+    Ok(args)
+}
+
+pub fn run_custom(input: &str, output_file: &str, custom: &CustomArgs, encoding: &EncodingSettings) -> Result<(), io::Error> {
+    run_ffmpeg(&custom_args(input, output_file, custom, encoding)?)
+}
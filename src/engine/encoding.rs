@@ -0,0 +1,381 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum VideoCodec {
+    #[default]
+    Auto, // whatever ffmpeg picks for the output extension
+    Copy,
+    X264,
+    X265,
+    Vp9,
+    Aom,
+    SvtAv1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum AudioCodec {
+    #[default]
+    Auto,
+    Copy,
+    Aac,
+    Opus,
+    Mp3,
+    Flac,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RateControl {
+    Crf(u32),
+    Bitrate(u32), // kbps
+}
+
+// Encoder settings shared by every operation that re-encodes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodingSettings {
+    pub video_codec: VideoCodec,
+    pub preset: Option<String>,
+    pub rate_control: Option<RateControl>,
+    pub pixel_format: Option<String>,
+    pub profile: Option<String>,
+    pub level: Option<String>,
+    pub audio_codec: AudioCodec,
+    pub audio_bitrate: Option<u32>, // kbps
+}
+
+impl Default for EncodingSettings {
+    fn default() -> Self {
+        EncodingSettings {
+            video_codec: VideoCodec::Auto,
+            preset: None,
+            rate_control: None,
+            pixel_format: None,
+            profile: None,
+            level: None,
+            audio_codec: AudioCodec::Auto,
+            audio_bitrate: None,
+        }
+    }
+}
+
+impl EncodingSettings {
+    // Video encoder options. A filtered stream has to be encoded, so `copy` is refused.
+    pub fn video_args(&self, filtered: bool) -> Result<Vec<String>, io::Error> {
+        let mut args: Vec<String> = vec![];
+        let Some(encoder) = self.video_codec.encoder() else {
+            return Ok(args);
+        };
+        if self.video_codec == VideoCodec::Copy {
+            if filtered {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "The video is filtered and can't be copied, choose a video codec",
+                ));
+            }
+            return Ok(vec!["-c:v".into(), "copy".into()]);
+        }
+
+        args.extend(["-c:v".into(), encoder.into()]);
+        args.extend(self.video_options("v"));
+        Ok(args)
+    }
+
+    // Options of the chosen video encoder for the streams `streams` selects: "v"
+    // for every video stream, or the index of one output stream
+    pub fn video_options(&self, streams: &str) -> Vec<String> {
+        let option = |name: &str| format!("-{}:{}", name, streams);
+        let mut args: Vec<String> = vec![];
+        if let Some(preset) = &self.preset {
+            match self.video_codec {
+                VideoCodec::Vp9 => args.extend([option("deadline"), "good".into(), option("cpu-used"), preset.clone()]),
+                VideoCodec::Aom => args.extend([option("cpu-used"), preset.clone()]),
+                _ => args.extend([option("preset"), preset.clone()]),
+            }
+        }
+        match self.rate_control {
+            Some(RateControl::Crf(crf)) => {
+                args.extend([option("crf"), crf.to_string()]);
+                // libvpx and libaom only use constant quality mode without a target bitrate
+                if matches!(self.video_codec, VideoCodec::Vp9 | VideoCodec::Aom) {
+                    args.extend([option("b"), "0".into()]);
+                }
+            }
+            Some(RateControl::Bitrate(kbps)) => args.extend([option("b"), format!("{}k", kbps)]),
+            None => {}
+        }
+        if let Some(pixel_format) = &self.pixel_format {
+            args.extend([option("pix_fmt"), pixel_format.clone()]);
+        }
+        if let Some(profile) = &self.profile {
+            args.extend([option("profile"), profile.clone()]);
+        }
+        if let Some(level) = &self.level {
+            args.extend([option("level"), level.clone()]);
+        }
+        args
+    }
+
+    pub fn audio_args(&self, filtered: bool) -> Result<Vec<String>, io::Error> {
+        let Some(encoder) = self.audio_codec.encoder() else {
+            return Ok(vec![]);
+        };
+        if self.audio_codec == AudioCodec::Copy && filtered {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The audio is filtered and can't be copied, choose an audio codec",
+            ));
+        }
+
+        let mut args: Vec<String> = vec!["-c:a".into(), encoder.into()];
+        args.extend(self.audio_options("a"));
+        Ok(args)
+    }
+
+    // Options of the chosen audio encoder, `streams` like in video_options
+    pub fn audio_options(&self, streams: &str) -> Vec<String> {
+        match self.audio_bitrate.filter(|_| self.audio_codec.has_bitrate()) {
+            Some(kbps) => vec![format!("-b:{}", streams), format!("{}k", kbps)],
+            None => vec![],
+        }
+    }
+}
+
+impl VideoCodec {
+    pub const ALL: [VideoCodec; 7] = [
+        VideoCodec::Auto,
+        VideoCodec::Copy,
+        VideoCodec::X264,
+        VideoCodec::X265,
+        VideoCodec::Vp9,
+        VideoCodec::Aom,
+        VideoCodec::SvtAv1,
+    ];
+
+    pub fn encoder(&self) -> Option<&'static str> {
+        match self {
+            VideoCodec::Auto => None,
+            VideoCodec::Copy => Some("copy"),
+            VideoCodec::X264 => Some("libx264"),
+            VideoCodec::X265 => Some("libx265"),
+            VideoCodec::Vp9 => Some("libvpx-vp9"),
+            VideoCodec::Aom => Some("libaom-av1"),
+            VideoCodec::SvtAv1 => Some("libsvtav1"),
+        }
+    }
+
+    // Name of the codec the encoder produces, as ffprobe reports it
+    pub fn codec_name(&self) -> Option<&'static str> {
+        match self {
+            VideoCodec::Auto | VideoCodec::Copy => None,
+            VideoCodec::X264 => Some("h264"),
+            VideoCodec::X265 => Some("hevc"),
+            VideoCodec::Vp9 => Some("vp9"),
+            VideoCodec::Aom | VideoCodec::SvtAv1 => Some("av1"),
+        }
+    }
+
+    // Speed presets, ordered from fastest to slowest
+    pub fn presets(&self) -> &'static [&'static str] {
+        match self {
+            VideoCodec::X264 | VideoCodec::X265 => &[
+                "ultrafast", "superfast", "veryfast", "faster", "fast", "medium", "slow", "slower", "veryslow",
+            ],
+            VideoCodec::Vp9 => &["5", "4", "3", "2", "1", "0"],
+            VideoCodec::Aom => &["8", "7", "6", "5", "4", "3", "2", "1", "0"],
+            VideoCodec::SvtAv1 => &["12", "10", "8", "6", "4", "2", "0"],
+            VideoCodec::Auto | VideoCodec::Copy => &[],
+        }
+    }
+
+    pub fn profiles(&self) -> &'static [&'static str] {
+        match self {
+            VideoCodec::X264 => &["baseline", "main", "high", "high10"],
+            VideoCodec::X265 => &["main", "main10"],
+            _ => &[],
+        }
+    }
+
+    // Sensible CRF for the codec, the scales differ between encoders
+    pub fn default_crf(&self) -> u32 {
+        match self {
+            VideoCodec::X265 => 28,
+            VideoCodec::Vp9 => 31,
+            VideoCodec::Aom | VideoCodec::SvtAv1 => 35,
+            _ => 23,
+        }
+    }
+
+    pub fn encodes(&self) -> bool {
+        !matches!(self, VideoCodec::Auto | VideoCodec::Copy)
+    }
+}
+
+impl AudioCodec {
+    pub const ALL: [AudioCodec; 6] = [
+        AudioCodec::Auto,
+        AudioCodec::Copy,
+        AudioCodec::Aac,
+        AudioCodec::Opus,
+        AudioCodec::Mp3,
+        AudioCodec::Flac,
+    ];
+
+    pub fn encoder(&self) -> Option<&'static str> {
+        match self {
+            AudioCodec::Auto => None,
+            AudioCodec::Copy => Some("copy"),
+            AudioCodec::Aac => Some("aac"),
+            AudioCodec::Opus => Some("libopus"),
+            AudioCodec::Mp3 => Some("libmp3lame"),
+            AudioCodec::Flac => Some("flac"),
+        }
+    }
+
+    pub fn codec_name(&self) -> Option<&'static str> {
+        match self {
+            AudioCodec::Auto | AudioCodec::Copy => None,
+            AudioCodec::Aac => Some("aac"),
+            AudioCodec::Opus => Some("opus"),
+            AudioCodec::Mp3 => Some("mp3"),
+            AudioCodec::Flac => Some("flac"),
+        }
+    }
+
+    pub fn has_bitrate(&self) -> bool {
+        matches!(self, AudioCodec::Aac | AudioCodec::Opus | AudioCodec::Mp3)
+    }
+}
+
+impl fmt::Display for VideoCodec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            VideoCodec::Auto => "Default for the format",
+            VideoCodec::Copy => "Copy (no re-encode)",
+            VideoCodec::X264 => "H.264 (libx264)",
+            VideoCodec::X265 => "H.265 (libx265)",
+            VideoCodec::Vp9 => "VP9 (libvpx-vp9)",
+            VideoCodec::Aom => "AV1 (libaom-av1)",
+            VideoCodec::SvtAv1 => "AV1 (libsvtav1)",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl fmt::Display for AudioCodec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            AudioCodec::Auto => "Default for the format",
+            AudioCodec::Copy => "Copy (no re-encode)",
+            AudioCodec::Aac => "AAC",
+            AudioCodec::Opus => "Opus (libopus)",
+            AudioCodec::Mp3 => "MP3 (libmp3lame)",
+            AudioCodec::Flac => "FLAC",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::command;
+    use crate::engine::convert::{convert, ContainerFormat};
+    use crate::engine::probe::{Probe, Stream};
+
+    fn crf(video_codec: VideoCodec, crf: u32) -> EncodingSettings {
+        EncodingSettings {
+            video_codec,
+            rate_control: Some(RateControl::Crf(crf)),
+            ..EncodingSettings::default()
+        }
+    }
+
+    #[test]
+    fn crf_alone_for_vp9_and_aom_needs_a_zero_bitrate() {
+        assert_eq!(crf(VideoCodec::Vp9, 31).video_args(true).unwrap(), ["-c:v", "libvpx-vp9", "-crf:v", "31", "-b:v", "0"]);
+        assert_eq!(crf(VideoCodec::Aom, 35).video_args(true).unwrap(), ["-c:v", "libaom-av1", "-crf:v", "35", "-b:v", "0"]);
+        assert_eq!(crf(VideoCodec::X264, 23).video_args(true).unwrap(), ["-c:v", "libx264", "-crf:v", "23"]);
+    }
+
+    #[test]
+    fn names_each_option_after_the_encoder() {
+        let settings = EncodingSettings {
+            preset: Some("2".to_string()),
+            pixel_format: Some("yuv420p10le".to_string()),
+            ..crf(VideoCodec::Vp9, 31)
+        };
+        let expected = ["-deadline:v", "good", "-cpu-used:v", "2", "-crf:v", "31", "-b:v", "0", "-pix_fmt:v", "yuv420p10le"];
+        assert_eq!(settings.video_options("v"), expected);
+        let settings = EncodingSettings {
+            preset: Some("slow".to_string()),
+            rate_control: Some(RateControl::Bitrate(2500)),
+            profile: Some("high".to_string()),
+            level: Some("4.1".to_string()),
+            ..crf(VideoCodec::X264, 0)
+        };
+        let expected = ["-preset:v", "slow", "-b:v", "2500k", "-profile:v", "high", "-level:v", "4.1"];
+        assert_eq!(settings.video_options("v"), expected);
+    }
+
+    #[test]
+    fn options_target_one_output_stream() {
+        let settings = EncodingSettings {
+            preset: Some("medium".to_string()),
+            audio_codec: AudioCodec::Opus,
+            audio_bitrate: Some(128),
+            ..crf(VideoCodec::X265, 28)
+        };
+        assert_eq!(settings.video_options("0"), ["-preset:0", "medium", "-crf:0", "28"]);
+        assert_eq!(settings.audio_options("2"), ["-b:2", "128k"]);
+        assert_eq!(settings.audio_args(true).unwrap(), ["-c:a", "libopus", "-b:a", "128k"]);
+    }
+
+    #[test]
+    fn copy_is_refused_for_filtered_streams() {
+        let copy = EncodingSettings {
+            video_codec: VideoCodec::Copy,
+            audio_codec: AudioCodec::Copy,
+            audio_bitrate: Some(192),
+            ..EncodingSettings::default()
+        };
+        assert_eq!(copy.video_args(false).unwrap(), ["-c:v", "copy"]);
+        assert_eq!(copy.video_args(true).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        // a bitrate means nothing to a copied stream
+        assert_eq!(copy.audio_args(false).unwrap(), ["-c:a", "copy"]);
+        assert_eq!(copy.audio_args(true).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn auto_leaves_the_choice_to_ffmpeg() {
+        let auto = EncodingSettings { audio_bitrate: Some(192), ..crf(VideoCodec::Auto, 23) };
+        assert!(auto.video_args(true).unwrap().is_empty());
+        assert!(auto.audio_args(true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn convert_gives_the_options_to_the_encoded_streams_only() {
+        let stream = |index: usize, kind: &str, codec: &str| Stream {
+            index,
+            codec_type: kind.to_string(),
+            codec_name: codec.to_string(),
+            ..Stream::default()
+        };
+        let probe = Probe {
+            streams: vec![stream(0, "video", "vp8"), stream(1, "audio", "aac"), stream(2, "subtitle", "subrip")],
+            ..Probe::default()
+        };
+        let settings = EncodingSettings {
+            audio_codec: AudioCodec::Aac,
+            audio_bitrate: Some(128),
+            ..crf(VideoCodec::X264, 20)
+        };
+        let (commands, result) = command::record(|| convert("in.webm", "out.mp4", &probe, ContainerFormat::Mp4, &settings));
+        assert!(result.is_ok());
+        let expected = [
+            "-hide_banner", "-i", "in.webm", "-map", "0:0", "-c:0", "libx264", "-crf:0", "20", "-map", "0:1", "-c:1", "copy",
+            "-map", "0:2", "-c:2", "mov_text", "-movflags", "+faststart", "out.mp4", "-n",
+        ];
+        assert_eq!(commands[0][1..], expected);
+    }
+}
@@ -0,0 +1,224 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FfmpegErrorKind {
+    MissingFile,
+    PermissionDenied,
+    UnknownEncoder,
+    InvalidFilter,
+    UnsupportedCodec,
+    DiskFull,
+    Other,
+}
+
+// A failed ffmpeg run: what went wrong in a sentence, and the full log for the details.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FfmpegError {
+    pub kind: FfmpegErrorKind,
+    pub message: String,
+    pub details: String,
+}
+
+impl FfmpegError {
+    // Reads the cause from ffmpeg's stderr, the most specific match wins.
+    pub fn from_stderr(stderr: &str) -> FfmpegError {
+        let lines: Vec<&str> = stderr.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+        let find = |patterns: &[&str]| lines.iter().find(|line| patterns.iter().any(|p| line.contains(p))).copied();
+
+        let (kind, message) = if let Some(line) = find(&["No space left on device"]) {
+            (FfmpegErrorKind::DiskFull, format!("The disk is full: {}", line))
+        } else if let Some(line) = find(&["Permission denied"]) {
+            let path = failed_path(&lines, line, ": Permission denied");
+            (FfmpegErrorKind::PermissionDenied, format!("No permission to access {}", path))
+        } else if let Some(line) = find(&["No such file or directory"]) {
+            let path = failed_path(&lines, line, ": No such file or directory");
+            (FfmpegErrorKind::MissingFile, format!("{} doesn't exist", path))
+        } else if let Some(line) = find(&["Unknown encoder", "Encoder not found"]) {
+            let encoder = line.split('\'').nth(1).unwrap_or(line);
+            (FfmpegErrorKind::UnknownEncoder, format!("Your ffmpeg build has no encoder {}", encoder))
+        } else if let Some(line) = find(&["codec not currently supported in container", "Could not find tag for codec"]) {
+            let codec = line.split("codec ").nth(1).and_then(|rest| rest.split_whitespace().next()).unwrap_or("");
+            let codec = codec.trim_end_matches(',');
+            (
+                FfmpegErrorKind::UnsupportedCodec,
+                format!("The output format can't hold the {} codec, pick another codec or container", codec),
+            )
+        } else if let Some(line) = find(&[
+            "Error parsing filterchain",
+            "Error parsing a filter description",
+            "Error initializing filter",
+            "Error applying option",
+            "No such filter",
+            "Error reinitializing filters",
+        ]) {
+            (FfmpegErrorKind::InvalidFilter, format!("A filter has invalid arguments: {}", line))
+        } else {
+            // ffmpeg closes with a generic summary, the cause is usually the line before
+            let generic = ["Conversion failed!", "Error opening output files: Invalid argument"];
+            let line = lines.iter().rev().find(|line| !generic.contains(line)).copied();
+            (FfmpegErrorKind::Other, line.unwrap_or("ffmpeg failed without a message").to_string())
+        };
+        FfmpegError {
+            kind,
+            message,
+            details: stderr.trim().to_string(),
+        }
+    }
+
+    // Errors raised outside ffmpeg keep their own message and have no details.
+    pub fn from_io(error: &io::Error) -> FfmpegError {
+        if let Some(error) = error.get_ref().and_then(|e| e.downcast_ref::<FfmpegError>()) {
+            return error.clone();
+        }
+        let kind = match error.kind() {
+            io::ErrorKind::NotFound => FfmpegErrorKind::MissingFile,
+            io::ErrorKind::PermissionDenied => FfmpegErrorKind::PermissionDenied,
+            _ => FfmpegErrorKind::Other,
+        };
+        FfmpegError {
+            kind,
+            message: error.to_string(),
+            details: String::new(),
+        }
+    }
+}
+
+// "in.mp4: No such file or directory" on older builds, newer ones name the
+// file on its own line: "Error opening input file in.mp4."
+fn failed_path(lines: &[&str], line: &str, suffix: &str) -> String {
+    let named = lines.iter().find_map(|line| {
+        line.strip_prefix("Error opening input file ")
+            .or_else(|| line.strip_prefix("Error opening output file "))
+    });
+    match named {
+        Some(path) => path.trim_end_matches('.').to_string(),
+        None if !line.starts_with('[') => line.split(suffix).next().unwrap_or(line).to_string(),
+        None => "a file".to_string(),
+    }
+}
+
+impl fmt::Display for FfmpegError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for FfmpegError {}
+
+impl From<FfmpegError> for io::Error {
+    fn from(error: FfmpegError) -> io::Error {
+        let kind = match error.kind {
+            FfmpegErrorKind::MissingFile => io::ErrorKind::NotFound,
+            FfmpegErrorKind::PermissionDenied => io::ErrorKind::PermissionDenied,
+            FfmpegErrorKind::UnknownEncoder | FfmpegErrorKind::InvalidFilter | FfmpegErrorKind::UnsupportedCodec => {
+                io::ErrorKind::InvalidInput
+            }
+            FfmpegErrorKind::DiskFull | FfmpegErrorKind::Other => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_input_on_ffmpeg_6() {
+        let error = FfmpegError::from_stderr("missing.mp4: No such file or directory\n");
+        assert_eq!(error.kind, FfmpegErrorKind::MissingFile);
+        assert_eq!(error.message, "missing.mp4 doesn't exist");
+    }
+
+    #[test]
+    fn missing_input_on_ffmpeg_7() {
+        let stderr = "[in#0 @ 0x55d1c2a0c340] Error opening input: No such file or directory
+Error opening input file missing.mp4.
+Error opening input files: No such file or directory
+";
+        let error = FfmpegError::from_stderr(stderr);
+        assert_eq!(error.kind, FfmpegErrorKind::MissingFile);
+        assert_eq!(error.message, "missing.mp4 doesn't exist");
+        assert_eq!(error.details, stderr.trim());
+    }
+
+    #[test]
+    fn permission_denied() {
+        let stderr = "Input #0, mov,mp4,m4a,3gp,3g2,mj2, from 'in.mp4':
+  Duration: 00:00:02.00, start: 0.000000, bitrate: 120 kb/s
+/root/out.mp4: Permission denied
+";
+        let error = FfmpegError::from_stderr(stderr);
+        assert_eq!(error.kind, FfmpegErrorKind::PermissionDenied);
+        assert_eq!(error.message, "No permission to access /root/out.mp4");
+    }
+
+    #[test]
+    fn unknown_encoder() {
+        let stderr = "[vost#0:0 @ 0x5632f0e3a6c0] Unknown encoder 'libsvtav1'
+Error opening output files: Encoder not found
+";
+        let error = FfmpegError::from_stderr(stderr);
+        assert_eq!(error.kind, FfmpegErrorKind::UnknownEncoder);
+        assert_eq!(error.message, "Your ffmpeg build has no encoder libsvtav1");
+    }
+
+    #[test]
+    fn codec_not_supported_by_the_container() {
+        let stderr = "[mp4 @ 0x55f0d6a4b8c0] Could not find tag for codec pcm_s16le in stream #1, codec not currently supported in container
+[out#0/mp4 @ 0x55f0d6a4b6c0] Could not write header (incorrect codec parameters ?): Invalid argument
+Conversion failed!
+";
+        let error = FfmpegError::from_stderr(stderr);
+        assert_eq!(error.kind, FfmpegErrorKind::UnsupportedCodec);
+        assert!(error.message.starts_with("The output format can't hold the pcm_s16le codec"));
+    }
+
+    #[test]
+    fn invalid_filter() {
+        let stderr = "[AVFilterGraph @ 0x5598b8a4f1c0] No such filter: 'zoompann'
+Error initializing complex filters.
+Invalid argument
+";
+        let error = FfmpegError::from_stderr(stderr);
+        assert_eq!(error.kind, FfmpegErrorKind::InvalidFilter);
+        assert!(error.message.ends_with("No such filter: 'zoompann'"));
+    }
+
+    #[test]
+    fn disk_full_wins_over_the_rest() {
+        let stderr = "[out#0/mp4 @ 0x55d0c8a3e2c0] Error muxing a packet
+av_interleaved_write_frame(): No space left on device
+Error writing trailer of out.mp4: No space left on device
+Conversion failed!
+";
+        let error = FfmpegError::from_stderr(stderr);
+        assert_eq!(error.kind, FfmpegErrorKind::DiskFull);
+    }
+
+    #[test]
+    fn other_errors_keep_the_line_before_the_summary() {
+        let stderr = "[libx264 @ 0x55e1a3c0b200] height not divisible by 2 (320x241)
+Error while opening encoder for output stream #0:0 - maybe incorrect parameters such as bit_rate, rate, width or height
+Conversion failed!
+";
+        let error = FfmpegError::from_stderr(stderr);
+        assert_eq!(error.kind, FfmpegErrorKind::Other);
+        assert!(error.message.starts_with("Error while opening encoder"));
+        assert_eq!(FfmpegError::from_stderr("\n").message, "ffmpeg failed without a message");
+    }
+
+    #[test]
+    fn survives_the_trip_through_io_error() {
+        let error = FfmpegError::from_stderr("missing.mp4: No such file or directory");
+        let io_error: io::Error = error.clone().into();
+        assert_eq!(io_error.kind(), io::ErrorKind::NotFound);
+        assert_eq!(FfmpegError::from_io(&io_error), error);
+
+        let plain = FfmpegError::from_io(&io::Error::new(io::ErrorKind::PermissionDenied, "denied"));
+        assert_eq!(plain.kind, FfmpegErrorKind::PermissionDenied);
+        assert!(plain.details.is_empty());
+    }
+}
@@ -0,0 +1,199 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum JobStatus {
+    #[default]
+    Pending,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Done | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+// Severity of an ffmpeg log line. ffmpeg only tags its lines with -loglevel level,
+// which would break the filter reports we parse, so it is read from how the
+// message starts, the way ffmpeg words its errors and warnings.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub enum LogLevel {
+    Error,
+    Warning,
+    #[default]
+    Info,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 3] = [LogLevel::Error, LogLevel::Warning, LogLevel::Info];
+
+    pub fn of(line: &str) -> LogLevel {
+        // "[libx264 @ 0x55d0c8a3e2c0] message", the context names who logged it
+        let mut message = line.trim();
+        while let Some((context, rest)) = message.strip_prefix('[').and_then(|rest| rest.split_once("] ")) {
+            if !context.contains(" @ ") {
+                break;
+            }
+            message = rest.trim_start();
+        }
+        let errors = [
+            "Error", "error ", "Could not", "Unable to", "Invalid", "Unknown encoder", "Unknown decoder", "Unrecognized option",
+            "No such filter", "Failed to", "Conversion failed", "Cannot",
+        ];
+        // "out.mp4: Permission denied", system errors follow the path they happened to
+        let causes = [
+            ": No such file or directory",
+            ": Permission denied",
+            ": No space left on device",
+            ": Invalid argument",
+            ": Invalid data found when processing input",
+        ];
+        let warnings = [
+            "Warning", "warning", "Past duration", "Non-monotonous DTS", "Non-monotonic DTS", "deprecated pixel format",
+            "Queue input is backward in time", "More than 1000 frames duplicated", "Guessed Channel Layout",
+        ];
+        if errors.iter().any(|prefix| message.starts_with(prefix)) || causes.iter().any(|cause| message.ends_with(cause)) {
+            LogLevel::Error
+        } else if warnings.iter().any(|prefix| message.starts_with(prefix)) {
+            LogLevel::Warning
+        } else {
+            LogLevel::Info
+        }
+    }
+}
+
+// Receives what ffmpeg prints while an operation runs on the current thread.
+pub struct Reporter {
+    pub on_progress: Box<dyn Fn(f32)>, // percent of the input duration
+    pub on_log: Box<dyn Fn(&str)>,
+    pub cancelled: Arc<AtomicBool>,
+}
+
+thread_local! {
+    static REPORTER: RefCell<Option<Reporter>> = const { RefCell::new(None) };
+}
+
+// Runs `work` with every ffmpeg call inside it reporting to `reporter`.
+pub fn with_reporter<T>(reporter: Reporter, work: impl FnOnce() -> T) -> T {
+    REPORTER.with(|current| *current.borrow_mut() = Some(reporter));
+    let result = work();
+    REPORTER.with(|current| *current.borrow_mut() = None);
+    result
+}
+
+// The cancel flag of the current job, to watch it from another thread.
+pub(super) fn cancel_flag() -> Option<Arc<AtomicBool>> {
+    REPORTER.with(|current| current.borrow().as_ref().map(|reporter| reporter.cancelled.clone()))
+}
+
+pub(super) fn is_cancelled() -> bool {
+    REPORTER.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .is_some_and(|reporter| reporter.cancelled.load(Ordering::Relaxed))
+    })
+}
+
+// Tracks the progress of one ffmpeg process from its stderr lines.
+#[derive(Debug, Default)]
+pub(super) struct ProgressParser {
+    duration: Option<f64>,
+}
+
+impl ProgressParser {
+    pub(super) fn feed(&mut self, line: &str) {
+        // "  Duration: 00:01:02.50, start: 0.000000, bitrate: 1205 kb/s"
+        if let Some(rest) = line.trim_start().strip_prefix("Duration: ") {
+            if self.duration.is_none() {
+                self.duration = rest.split(',').next().and_then(parse_timestamp).filter(|d| *d > 0.0);
+            }
+        }
+        let progress = line
+            .split_once("time=")
+            .and_then(|(_, rest)| rest.split_whitespace().next())
+            .and_then(parse_timestamp)
+            .zip(self.duration)
+            .map(|(time, duration)| (time / duration * 100.0).clamp(0.0, 100.0) as f32);
+
+        REPORTER.with(|current| {
+            if let Some(reporter) = current.borrow().as_ref() {
+                // progress lines repeat a few times a second, they would drown the log
+                match progress {
+                    Some(progress) => (reporter.on_progress)(progress),
+                    None => (reporter.on_log)(line),
+                }
+            }
+        });
+    }
+}
+
+// "00:01:02.50" => 62.5
+pub(super) fn parse_timestamp(value: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    for part in value.trim().split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(seconds)
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            LogLevel::Error => "Errors",
+            LogLevel::Warning => "Warnings",
+            LogLevel::Info => "Everything",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            JobStatus::Pending => "Pending",
+            JobStatus::Running => "Running",
+            JobStatus::Done => "Done",
+            JobStatus::Failed => "Failed",
+            JobStatus::Cancelled => "Cancelled",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_level_from_the_message_start() {
+        assert_eq!(LogLevel::of("[in#0 @ 0x55d1c2a0c340] Error opening input: No such file or directory"), LogLevel::Error);
+        assert_eq!(LogLevel::of("Error opening input file missing.mp4."), LogLevel::Error);
+        assert_eq!(LogLevel::of("[vost#0:0 @ 0x5632f0e3a6c0] Unknown encoder 'libsvtav1'"), LogLevel::Error);
+        assert_eq!(LogLevel::of("/root/out.mp4: Permission denied"), LogLevel::Error);
+        assert_eq!(LogLevel::of("Conversion failed!"), LogLevel::Error);
+        assert_eq!(LogLevel::of("[mp4 @ 0x55f0d6a4b8c0] Non-monotonous DTS in output stream 0:1"), LogLevel::Warning);
+        assert_eq!(LogLevel::of("[swscaler @ 0x7f0c] deprecated pixel format used, make sure you did set range correctly"), LogLevel::Warning);
+    }
+
+    #[test]
+    fn words_inside_a_message_are_not_a_level() {
+        let lines = [
+            "  Stream #0:0(und): Video: h264 (High), yuv420p(tv, unknown/bt709/bt709), 1920x1080",
+            "    title           : Invalid Error Cannot",
+            "[Parsed_astats_1 @ 0x55f2] Number of Infs: 0",
+            "[Parsed_silencedetect_0 @ 0x55f4] silence_end: 4.2 | silence_duration: 1.1",
+            "[libx264 @ 0x55e1a3c0b200] frame I:1     Avg QP:20.12  size: 41022",
+            "Press [q] to stop, [?] for help",
+        ];
+        for line in lines {
+            assert_eq!(LogLevel::of(line), LogLevel::Info, "{}", line);
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use super::encoding::EncodingSettings;
+use super::run_ffmpeg_stderr;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessTarget {
+    pub integrated: f64, // LUFS
+    pub true_peak: f64,  // dBTP
+    pub range: f64,      // LU
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum LoudnessPreset {
+    #[default]
+    Streaming,
+    Podcast,
+    Broadcast,
+    Custom,
+}
+
+// loudnorm prints its measurements as strings, e.g. "input_i" : "-27.61"
+#[derive(Debug, Clone, Deserialize)]
+struct LoudnormMeasurement {
+    input_i: String,
+    input_tp: String,
+    input_lra: String,
+    input_thresh: String,
+    target_offset: String,
+}
+
+// Two-pass EBU R128 normalization: the first pass measures the input, the second
+// applies a linear gain so the result hits `target` without pumping.
+pub fn normalize(
+    input: &str,
+    output_file: &str,
+    target: &LoudnessTarget,
+    encoding: &EncodingSettings,
+) -> Result<(), io::Error> {
+    let encode_args = encoding.audio_args(true)?;
+    let loudnorm = format!(
+        "loudnorm=I={}:TP={}:LRA={}",
+        target.integrated, target.true_peak, target.range
+    );
+
+    let analysis = run_ffmpeg_stderr(&[
+        "-hide_banner".into(),
+        "-i".into(),
+        input.into(),
+        "-vn".into(),
+        "-af".into(),
+        format!("{}:print_format=json", loudnorm),
+        "-f".into(),
+        "null".into(),
+        "-".into(),
+    ])?;
+    let measured = parse_loudnorm(&analysis)?;
+
+    let mut args: Vec<String> = vec![
+        "-hide_banner".into(),
+        "-i".into(),
+        input.into(),
+        "-af".into(),
+        format!(
+            "{}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true:print_format=summary",
+            loudnorm,
+            measured.input_i,
+            measured.input_tp,
+            measured.input_lra,
+            measured.input_thresh,
+            measured.target_offset
+        ),
+        // loudnorm resamples to 192 kHz internally
+        "-ar".into(),
+        "48000".into(),
+        "-c:v".into(),
+        "copy".into(),
+    ];
+    args.extend(encode_args);
+    args.push(output_file.into());
+    args.push("-n".into());
+    run_ffmpeg_stderr(&args)?;
+
+    Ok(())
+}
+
+// The JSON block is the last thing loudnorm writes to stderr.
+fn parse_loudnorm(stderr: &str) -> Result<LoudnormMeasurement, io::Error> {
+    let start = stderr.rfind('{');
+    let end = stderr.rfind('}');
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => serde_json::from_str(&stderr[start..=end])
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "loudnorm did not print any measurement",
+        )),
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LoudnessReport {
+    pub integrated: Option<f64>,  // LUFS
+    pub range: Option<f64>,       // LU
+    pub true_peak: Option<f64>,   // dBFS
+    pub mean_volume: Option<f64>, // dB
+    pub max_volume: Option<f64>,  // dB
+    pub channels: Vec<ChannelLevels>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ChannelLevels {
+    pub channel: u32,
+    pub rms: Option<f64>,  // dB
+    pub peak: Option<f64>, // dB
+}
+
+// Runs ebur128, astats and volumedetect in a single decode and collects what they
+// print into a report.
+pub fn analyze(input: &str) -> Result<LoudnessReport, io::Error> {
+    let stderr = run_ffmpeg_stderr(&[
+        "-hide_banner".into(),
+        "-nostats".into(),
+        "-i".into(),
+        input.into(),
+        "-vn".into(),
+        "-af".into(),
+        "ebur128=peak=true,astats,volumedetect".into(),
+        "-f".into(),
+        "null".into(),
+        "-".into(),
+    ])?;
+
+    Ok(parse_report(&stderr))
+}
+
+lazy_static::lazy_static! {
+    static ref INTEGRATED: Regex = Regex::new(r"I:\s+(-?[\d.]+) LUFS").unwrap();
+    static ref RANGE: Regex = Regex::new(r"LRA:\s+(-?[\d.]+) LU").unwrap();
+    static ref TRUE_PEAK: Regex = Regex::new(r"Peak:\s+(-?[\d.]+) dBFS").unwrap();
+    static ref MEAN_VOLUME: Regex = Regex::new(r"mean_volume:\s+(-?[\d.]+) dB").unwrap();
+    static ref MAX_VOLUME: Regex = Regex::new(r"max_volume:\s+(-?[\d.]+) dB").unwrap();
+}
+
+fn parse_report(stderr: &str) -> LoudnessReport {
+    let number = |re: &Regex, text: &str| -> Option<f64> { re.captures(text).and_then(|c| c[1].parse().ok()) };
+
+    let mut report = LoudnessReport::default();
+
+    // ebur128 also logs running values, only the final summary is of interest
+    if let Some(start) = stderr.rfind("Summary:") {
+        let summary = &stderr[start..];
+        report.integrated = number(&INTEGRATED, summary);
+        report.range = number(&RANGE, summary);
+        report.true_peak = number(&TRUE_PEAK, summary);
+    }
+    report.mean_volume = number(&MEAN_VOLUME, stderr);
+    report.max_volume = number(&MAX_VOLUME, stderr);
+
+    for line in stderr.lines().filter(|l| l.contains("Parsed_astats")) {
+        let Some((_, value)) = line.split_once("] ") else {
+            continue;
+        };
+        if value.trim() == "Overall" {
+            break;
+        }
+        if let Some(channel) = value.strip_prefix("Channel:") {
+            report.channels.push(ChannelLevels {
+                channel: channel.trim().parse().unwrap_or_default(),
+                ..Default::default()
+            });
+        } else if let Some(last) = report.channels.last_mut() {
+            if let Some(rms) = value.strip_prefix("RMS level dB:") {
+                last.rms = rms.trim().parse().ok();
+            } else if let Some(peak) = value.strip_prefix("Peak level dB:") {
+                last.peak = peak.trim().parse().ok();
+            }
+        }
+    }
+
+    report
+}
+
+impl LoudnessReport {
+    pub fn to_json(&self) -> Result<String, io::Error> {
+        serde_json::to_string_pretty(self).map_err(io::Error::other)
+    }
+}
+
+impl LoudnessPreset {
+    pub fn target(&self) -> Option<LoudnessTarget> {
+        match self {
+            LoudnessPreset::Streaming => Some(LoudnessTarget { integrated: -14.0, true_peak: -1.0, range: 11.0 }),
+            LoudnessPreset::Podcast => Some(LoudnessTarget { integrated: -16.0, true_peak: -1.5, range: 11.0 }),
+            LoudnessPreset::Broadcast => Some(LoudnessTarget { integrated: -23.0, true_peak: -1.0, range: 7.0 }),
+            LoudnessPreset::Custom => None,
+        }
+    }
+}
+
+impl fmt::Display for LoudnessPreset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            LoudnessPreset::Streaming => "Streaming (-14 LUFS)",
+            LoudnessPreset::Podcast => "Podcast (-16 LUFS)",
+            LoudnessPreset::Broadcast => "Broadcast EBU R128 (-23 LUFS)",
+            LoudnessPreset::Custom => "Custom",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_loudnorm_measurement() {
+        let stderr = "Stream mapping:
+  Stream #0:1 -> #0:0 (aac (native) -> pcm_s16le (native))
+[Parsed_loudnorm_0 @ 0x5566d7a0e3c0] 
+{
+	\"input_i\" : \"-27.61\",
+	\"input_tp\" : \"-4.47\",
+	\"input_lra\" : \"18.06\",
+	\"input_thresh\" : \"-39.20\",
+	\"output_i\" : \"-16.58\",
+	\"output_tp\" : \"-1.50\",
+	\"output_lra\" : \"14.78\",
+	\"output_thresh\" : \"-27.71\",
+	\"normalization_type\" : \"dynamic\",
+	\"target_offset\" : \"0.58\"
+}
+[out#0/null @ 0x5566d7a0d7c0] video:0KiB audio:1034KiB subtitle:0KiB other streams:0KiB
+";
+        let measured = parse_loudnorm(stderr).unwrap();
+        assert_eq!(measured.input_i, "-27.61");
+        assert_eq!(measured.input_tp, "-4.47");
+        assert_eq!(measured.input_lra, "18.06");
+        assert_eq!(measured.input_thresh, "-39.20");
+        assert_eq!(measured.target_offset, "0.58");
+    }
+
+    #[test]
+    fn fails_without_a_measurement() {
+        let error = parse_loudnorm("Output file is empty, nothing was encoded\n").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let error = parse_loudnorm("[Parsed_loudnorm_0 @ 0x5566d7a0e3c0] \n{\n\t\"input_i\" : \"-inf\",\n}").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reads_the_report_of_ebur128_astats_and_volumedetect() {
+        let stderr = "[Parsed_ebur128_0 @ 0x55f1] t: 0.0999773  TARGET:-23 LUFS    M:-120.7 S:-120.7     I: -70.0 LUFS       LRA:   0.0 LU  FTPK: -12.1 dBFS  TPK: -12.1 dBFS
+[Parsed_ebur128_0 @ 0x55f1] Summary:
+
+  Integrated loudness:
+    I:         -19.5 LUFS
+    Threshold: -30.0 LUFS
+
+  Loudness range:
+    LRA:         6.1 LU
+    Threshold: -40.1 LUFS
+    LRA low:   -24.3 LUFS
+    LRA high:  -18.2 LUFS
+
+  True peak:
+    Peak:       -0.3 dBFS
+[Parsed_astats_1 @ 0x55f2] Channel: 1
+[Parsed_astats_1 @ 0x55f2] DC offset: 0.000012
+[Parsed_astats_1 @ 0x55f2] Peak level dB: -0.302
+[Parsed_astats_1 @ 0x55f2] RMS level dB: -20.153
+[Parsed_astats_1 @ 0x55f2] Channel: 2
+[Parsed_astats_1 @ 0x55f2] Peak level dB: -1.250
+[Parsed_astats_1 @ 0x55f2] RMS level dB: -inf
+[Parsed_astats_1 @ 0x55f2] Overall
+[Parsed_astats_1 @ 0x55f2] Peak level dB: -0.302
+[Parsed_astats_1 @ 0x55f2] RMS level dB: -21.010
+[Parsed_volumedetect_2 @ 0x55f3] n_samples: 2646000
+[Parsed_volumedetect_2 @ 0x55f3] mean_volume: -20.1 dB
+[Parsed_volumedetect_2 @ 0x55f3] max_volume: -0.3 dB
+";
+        let report = parse_report(stderr);
+        assert_eq!(report.integrated, Some(-19.5));
+        assert_eq!(report.range, Some(6.1));
+        assert_eq!(report.true_peak, Some(-0.3));
+        assert_eq!(report.mean_volume, Some(-20.1));
+        assert_eq!(report.max_volume, Some(-0.3));
+        assert_eq!(report.channels.len(), 2);
+        assert_eq!(report.channels[0], ChannelLevels { channel: 1, rms: Some(-20.153), peak: Some(-0.302) });
+        // "-inf" parses as a float, silence has no finite level
+        assert_eq!(report.channels[1].peak, Some(-1.25));
+        assert!(report.channels[1].rms.is_some_and(|rms| rms.is_infinite()));
+    }
+
+    #[test]
+    fn leaves_out_what_was_not_printed() {
+        let report = parse_report("[Parsed_volumedetect_2 @ 0x55f3] mean_volume: -91.0 dB\n");
+        assert_eq!(report.mean_volume, Some(-91.0));
+        assert_eq!(report, LoudnessReport { mean_volume: Some(-91.0), ..LoudnessReport::default() });
+    }
+}
@@ -0,0 +1,109 @@
+use super::error::FfmpegError;
+use super::ffprobe;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Probe {
+    #[serde(default)]
+    pub streams: Vec<Stream>,
+    #[serde(default)]
+    pub format: Format,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Stream {
+    pub index: usize,
+    #[serde(default)]
+    pub codec_type: String,
+    #[serde(default)]
+    pub codec_name: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub channels: Option<u32>,
+    pub channel_layout: Option<String>,
+    pub sample_rate: Option<String>,
+    pub bit_rate: Option<String>,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+    #[serde(default)]
+    pub disposition: HashMap<String, i32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Format {
+    #[serde(default)]
+    pub format_name: String,
+    pub duration: Option<String>,
+    pub bit_rate: Option<String>,
+}
+
+// Reads the streams and container information of `input` with ffprobe.
+pub fn probe(input: &str) -> Result<Probe, io::Error> {
+    let output = ffprobe()
+        .arg("-v")
+        .arg("error")
+        .arg("-show_streams")
+        .arg("-show_format")
+        .arg("-of")
+        .arg("json")
+        .arg(input)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(FfmpegError::from_stderr(&String::from_utf8_lossy(&output.stderr)).into());
+    }
+
+    serde_json::from_slice(&output.stdout).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+impl Probe {
+    pub fn streams_of(&self, codec_type: &str) -> Vec<&Stream> {
+        self.streams.iter().filter(|s| s.codec_type == codec_type).collect()
+    }
+
+    pub fn audio_streams(&self) -> Vec<&Stream> {
+        self.streams_of("audio")
+    }
+
+    pub fn duration(&self) -> Option<f64> {
+        self.format.duration.as_ref().and_then(|d| d.parse().ok())
+    }
+}
+
+impl Stream {
+    pub fn language(&self) -> Option<&str> {
+        self.tags.get("language").map(|s| s.as_str())
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.tags.get("title").map(|s| s.as_str())
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.disposition.get("default").is_some_and(|d| *d == 1)
+    }
+}
+
+impl fmt::Display for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{} {} {}", self.index, self.codec_type, self.codec_name)?;
+        if let Some(layout) = &self.channel_layout {
+            write!(f, " {}", layout)?;
+        } else if let Some(channels) = self.channels {
+            write!(f, " {}ch", channels)?;
+        }
+        if let (Some(width), Some(height)) = (self.width, self.height) {
+            write!(f, " {}x{}", width, height)?;
+        }
+        if let Some(language) = self.language() {
+            write!(f, " [{}]", language)?;
+        }
+        if let Some(title) = self.title() {
+            write!(f, " \"{}\"", title)?;
+        }
+        Ok(())
+    }
+}
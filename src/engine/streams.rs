@@ -0,0 +1,156 @@
+use super::convert::ContainerFormat;
+use super::probe::Stream;
+use super::run_ffmpeg;
+use std::io;
+
+// An audio or subtitle stream as it should end up in the remuxed output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    pub input: usize,  // index into the input files, 0 is the source video
+    pub stream: usize, // absolute stream index inside that input
+    pub kind: String,  // "audio" or "subtitle"
+    pub codec: String,
+    pub enabled: bool,
+    pub language: String,
+    pub title: String,
+    pub default: bool,
+    pub tags: (String, String), // language and title as probed, ffmpeg copies them
+}
+
+impl Track {
+    pub fn from_stream(input: usize, stream: &Stream) -> Track {
+        Track {
+            input,
+            stream: stream.index,
+            kind: stream.codec_type.clone(),
+            codec: stream.codec_name.clone(),
+            enabled: true,
+            language: stream.language().unwrap_or_default().to_string(),
+            title: stream.title().unwrap_or_default().to_string(),
+            default: stream.is_default(),
+            tags: (
+                stream.language().unwrap_or_default().to_string(),
+                stream.title().unwrap_or_default().to_string(),
+            ),
+        }
+    }
+
+    // Output stream specifier type, e.g. the `a` in -metadata:s:a:0
+    fn specifier(&self) -> &'static str {
+        if self.kind == "subtitle" {
+            "s"
+        } else {
+            "a"
+        }
+    }
+}
+
+// Matroska is the only container here that holds attachments such as subtitle fonts.
+pub fn keeps_attachments(output_file: &str) -> bool {
+    ContainerFormat::of_file(output_file) == Some(ContainerFormat::Mkv)
+}
+
+// Codec option of `track` in `output_file`: text subtitles are converted to the
+// ones the container takes, other tracks are copied or refused before ffmpeg runs.
+fn track_codec(output_file: &str, track: &Track) -> Result<&'static str, io::Error> {
+    let Some(container) = ContainerFormat::of_file(output_file) else {
+        return Ok("copy");
+    };
+    if container.supports(&track.kind, &track.codec) {
+        return Ok("copy");
+    }
+    match container.encoder(&track.kind, &track.codec) {
+        Some(encoder) if track.kind == "subtitle" => Ok(encoder),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} can't hold the {} track #{} ({}), pick another output format",
+                container, track.kind, track.stream, track.codec
+            ),
+        )),
+    }
+}
+
+// Remuxes the video of the first input together with the enabled tracks, in the
+// given order, only text subtitles are re-encoded. Attachments of the first input
+// are kept when the output can hold them, data streams are left out.
+pub fn remux_tracks(inputs: &[String], output_file: &str, tracks: &[Track]) -> Result<(), io::Error> {
+    let mut args: Vec<String> = vec!["-hide_banner".into()];
+    for input in inputs {
+        args.push("-i".into());
+        args.push(input.clone());
+    }
+    args.extend(["-map".into(), "0:v?".into()]);
+
+    let enabled: Vec<&Track> = tracks.iter().filter(|t| t.enabled).collect();
+    for track in &enabled {
+        args.push("-map".into());
+        args.push(format!("{}:{}", track.input, track.stream));
+    }
+    if keeps_attachments(output_file) {
+        args.extend(["-map".into(), "0:t?".into()]);
+    }
+    args.extend(["-c".into(), "copy".into()]);
+
+    let (mut audio, mut subtitle) = (0, 0);
+    for track in &enabled {
+        let counter = if track.specifier() == "s" { &mut subtitle } else { &mut audio };
+        let target = format!("{}:{}", track.specifier(), counter);
+        *counter += 1;
+
+        let codec = track_codec(output_file, track)?;
+        if codec != "copy" {
+            args.push(format!("-c:{}", target));
+            args.push(codec.into());
+        }
+
+        // untouched tags are copied as they are, an emptied one is cleared
+        let (language, title) = (track.language.trim(), track.title.trim());
+        if language != track.tags.0 {
+            args.push(format!("-metadata:s:{}", target));
+            args.push(format!("language={}", language));
+        }
+        if title != track.tags.1 {
+            args.push(format!("-metadata:s:{}", target));
+            args.push(format!("title={}", title));
+        }
+        args.push(format!("-disposition:{}", target));
+        args.push(if track.default { "default".into() } else { "0".into() });
+    }
+    args.push(output_file.into());
+    args.push("-n".into());
+
+    run_ffmpeg(&args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(kind: &str, codec: &str) -> Track {
+        let stream = Stream {
+            index: 2,
+            codec_type: kind.to_string(),
+            codec_name: codec.to_string(),
+            ..Stream::default()
+        };
+        Track::from_stream(1, &stream)
+    }
+
+    #[test]
+    fn converts_text_subtitles_for_the_output() {
+        assert_eq!(track_codec("out.mp4", &track("subtitle", "subrip")).unwrap(), "mov_text");
+        assert_eq!(track_codec("out.MOV", &track("subtitle", "ass")).unwrap(), "mov_text");
+        assert_eq!(track_codec("out.mkv", &track("subtitle", "mov_text")).unwrap(), "srt");
+        assert_eq!(track_codec("out.mkv", &track("subtitle", "ass")).unwrap(), "copy");
+        assert_eq!(track_codec("out.mp4", &track("audio", "aac")).unwrap(), "copy");
+        assert_eq!(track_codec("out.avi", &track("subtitle", "subrip")).unwrap(), "copy");
+    }
+
+    #[test]
+    fn refuses_tracks_the_output_cannot_hold() {
+        let error = track_codec("out.mp4", &track("subtitle", "hdmv_pgs_subtitle")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(track_codec("out.webm", &track("audio", "aac")).is_err());
+    }
+}
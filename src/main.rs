@@ -1,23 +1,54 @@
+mod cli;
 mod engine;
 mod font_installer;
+mod operations;
+mod presets;
+mod queue;
 mod remixicon;
+mod settings;
 
-use crate::engine::{mute, rotate};
+use crate::engine::audio::{detect_silence, ChannelMapping, SilentRange};
+use crate::engine::chain::{Position, Step, Turn};
+use crate::engine::command;
+use crate::engine::capabilities::{detect, Capabilities, Requirements, MIN_VERSION};
+use crate::engine::convert::ContainerFormat;
+use crate::engine::encoding::{AudioCodec, VideoCodec};
+use crate::engine::error::FfmpegError;
+use crate::engine::jobs::{JobStatus, LogLevel};
+use crate::engine::loudness::{LoudnessPreset, LoudnessReport};
+use crate::engine::probe::{Probe, Stream};
+use crate::engine::streams::{keeps_attachments, Track};
+use crate::engine::{free_path, same_file, set_binaries, Binaries};
+use crate::engine::{list_files, list_images, AnimationFormat, AudioTarget, Dither, ImageFormat, SlideshowMode};
+use crate::operations::{non_empty, Inputs};
+use crate::presets::{ChainStep, FrameMode, Preset, RateMode, SavedPreset, StepKind};
+use crate::queue::{in_background, Job, JobEvent, JobOutput, Operation};
 use crate::remixicon::remix_icon;
-use iced::widget::{button, column, combo_box, container, horizontal_space, progress_bar, row, text, Column, Container};
+use iced::widget::scrollable::Anchor;
+use iced::widget::{button, checkbox, column, combo_box, container, horizontal_space, pick_list, progress_bar, rich_text, row, scrollable, span, text, text_editor, text_input, Button, Column, Container};
 use iced::Alignment::End;
-use iced::{Center, Element, Fill, Task, Theme, Color, Border};
+use iced::{Center, Element, Fill, Font, Task, Theme, Color, Border};
 use remixicon::remix_init;
 use std::{fmt, io};
 use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
 
 fn theme(state: &Controller) -> Theme {
-    print!("{}", state.value.to_string());
+    print!("{}", state.value);
     Theme::CatppuccinMocha
 }
 
 #[tokio::main]
-pub async fn main() -> iced::Result {
+pub async fn main() -> ExitCode {
+    // `ffmpeg-owl-ui cli ...` runs one operation without opening the window
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "cli") {
+        return cli::run(&args[1..]);
+    }
+
     // install icon font
     let font_path = "node_modules/remixicon/fonts/remixicon.ttf"; // Path to the font file
     match font_installer::install_font(font_path) {
@@ -28,9 +59,16 @@ pub async fn main() -> iced::Result {
     // init icon font
     remix_init();
 
-    iced::application("FFmpeg owl ui", Controller::update, Controller::view)
+    let result = iced::application("FFmpeg owl ui", Controller::update, Controller::view)
         .theme(theme)
-        .run_with(Controller::new)
+        .run_with(Controller::new);
+    match result {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
 
 
@@ -42,6 +80,31 @@ enum Rotation {
     R180, // 180 deg rotate
 }
 
+const VIDEO_EXTENSIONS: [&str; 6] = ["mp4", "mkv", "mov", "webm", "ts", "avi"];
+
+#[derive(Debug, Clone, PartialEq, Default)]
+enum FfmpegStatus {
+    #[default]
+    Ready,
+    Checking,
+    Missing(String),
+    Outdated(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SpeedPreset(f64);
+
+const SPEED_PRESETS: [SpeedPreset; 8] = [
+    SpeedPreset(0.25),
+    SpeedPreset(0.5),
+    SpeedPreset(0.75),
+    SpeedPreset(1.25),
+    SpeedPreset(1.5),
+    SpeedPreset(2.0),
+    SpeedPreset(3.0),
+    SpeedPreset(4.0),
+];
+
 
 #[derive(Default)]
 struct Controller {
@@ -56,6 +119,87 @@ struct Controller {
     toasts: Vec<HashMap<String, String>>,
     rotates: combo_box::State<Rotation>,
     selected_rot: Option<Rotation>,
+    speed_factor: String,
+    speed_drop_audio: bool,
+    anim_format: AnimationFormat,
+    anim_fps: String,
+    anim_width: String,
+    anim_dither: Dither,
+    anim_loop: String,
+    anim_start: String,
+    anim_end: String,
+    frames_mode: FrameMode,
+    frames_format: ImageFormat,
+    frames_interval: String,
+    frames_timestamp: String,
+    frames_pattern: String,
+    slide_images: Vec<String>,
+    slide_mode: SlideshowMode,
+    slide_fps: String,
+    slide_size: String,
+    slide_duration: String,
+    slide_crossfade: String,
+    slide_ken_burns: bool,
+    slide_audio: String,
+    probe: Option<Probe>,
+    extract_stream: Option<Stream>,
+    extract_target: AudioTarget,
+    extract_bitrate: String,
+    norm_preset: LoudnessPreset,
+    norm_integrated: String,
+    norm_true_peak: String,
+    norm_range: String,
+    loudness_report: Option<LoudnessReport>,
+    adjust_gain: String,
+    adjust_fade_in: String,
+    adjust_fade_out: String,
+    adjust_offset: String,
+    silence_threshold: String,
+    silence_min: String,
+    silence_padding: String,
+    silences: Vec<SilentRange>,
+    detecting_silence: bool,
+    channel_mapping: ChannelMapping,
+    track_inputs: Vec<String>,
+    tracks: Vec<Track>,
+    container_format: ContainerFormat,
+    enc_video_codec: VideoCodec,
+    enc_preset: Option<String>,
+    enc_rate_mode: RateMode,
+    enc_quality: String,
+    enc_pixel_format: String,
+    enc_profile: Option<String>,
+    enc_level: String,
+    enc_audio_codec: AudioCodec,
+    enc_audio_bitrate: String,
+    capabilities: Option<Capabilities>,
+    ffmpeg_status: FfmpegStatus,
+    ffmpeg_path: String,
+    ffprobe_path: String,
+    jobs: Vec<Job>,
+    next_job_id: usize,
+    selected_job: Option<usize>,
+    log_level: LogLevel,
+    log_search: String,
+    log_autoscroll: bool,
+    queue_paused: bool,
+    max_jobs: String,
+    batch_inputs: Vec<String>,
+    batch_dir: String,
+    batch_template: String,
+    chain_steps: Vec<ChainStep>,
+    custom_input_options: String,
+    custom_output_options: String,
+    custom_filter: String,
+    saved_presets: Vec<SavedPreset>,
+    preset_name: String,
+    pending_outputs: Option<(Vec<(String, String)>, Operation)>,
+    command_preview: bool,
+    command_editable: bool,
+    command: text_editor::Content,
+    // (input, output, settings) the preview was made for, edits only run with them
+    command_for: Option<(String, String, Preset)>,
+    command_note: Option<String>,
 }
 
 
@@ -82,7 +226,146 @@ enum Message {
     SelectImage,
     ImageOpened(Result<String, String>),
     RotateSelected(Rotation),
-
+    Speed,
+    SpeedChanged(String),
+    SpeedPresetSelected(SpeedPreset),
+    SpeedDropAudioToggled(bool),
+    Animation,
+    AnimationFormatSelected(AnimationFormat),
+    AnimationFpsChanged(String),
+    AnimationWidthChanged(String),
+    AnimationDitherSelected(Dither),
+    AnimationLoopChanged(String),
+    AnimationStartChanged(String),
+    AnimationEndChanged(String),
+    Frames,
+    SelectOutputFolder,
+    OutputFolderOpened(Result<String, String>),
+    FrameModeSelected(FrameMode),
+    FrameFormatSelected(ImageFormat),
+    FrameIntervalChanged(String),
+    FrameTimestampChanged(String),
+    FramePatternChanged(String),
+    Slideshow,
+    SelectImages,
+    ImagesOpened(Result<Vec<String>, String>),
+    SelectImageFolder,
+    ImageFolderOpened(Result<String, String>),
+    SlideshowModeSelected(SlideshowMode),
+    SlideshowFpsChanged(String),
+    SlideshowSizeChanged(String),
+    SlideshowDurationChanged(String),
+    SlideshowCrossfadeChanged(String),
+    SlideshowKenBurnsToggled(bool),
+    SelectSlideshowAudio,
+    SlideshowAudioOpened(Result<String, String>),
+    ExtractAudio,
+    ExtractStreamSelected(Box<Stream>),
+    ExtractTargetSelected(AudioTarget),
+    ExtractBitrateChanged(String),
+    Normalize,
+    NormalizePresetSelected(LoudnessPreset),
+    NormalizeIntegratedChanged(String),
+    NormalizeTruePeakChanged(String),
+    NormalizeRangeChanged(String),
+    Analyze,
+    ExportReport,
+    ReportPathChosen(Result<String, String>),
+    AdjustAudio,
+    AdjustGainChanged(String),
+    AdjustFadeInChanged(String),
+    AdjustFadeOutChanged(String),
+    AdjustOffsetChanged(String),
+    RemoveSilence,
+    SilenceThresholdChanged(String),
+    SilenceMinChanged(String),
+    SilencePaddingChanged(String),
+    DetectSilence,
+    SilenceDetected((String, f64, f64), Result<Vec<SilentRange>, FfmpegError>),
+    Channels,
+    ChannelMappingSelected(ChannelMapping),
+    Streams,
+    TrackToggled(usize, bool),
+    TrackMovedUp(usize),
+    TrackMovedDown(usize),
+    TrackLanguageChanged(usize, String),
+    TrackTitleChanged(usize, String),
+    TrackDefaultToggled(usize, bool),
+    AddTrack,
+    TrackFileOpened(Result<String, String>),
+    Convert,
+    ContainerSelected(ContainerFormat),
+    VideoCodecSelected(VideoCodec),
+    EncoderPresetSelected(String),
+    RateModeSelected(RateMode),
+    QualityChanged(String),
+    PixelFormatChanged(String),
+    ProfileSelected(String),
+    LevelChanged(String),
+    AudioCodecSelected(AudioCodec),
+    AudioBitrateChanged(String),
+    Settings,
+    FfmpegPathChanged(String),
+    FfprobePathChanged(String),
+    BrowseFfmpeg,
+    FfmpegBinaryOpened(Result<String, String>),
+    BrowseFfprobe,
+    FfprobeBinaryOpened(Result<String, String>),
+    SaveSettings,
+    FfmpegChecked(Binaries, bool, Result<Capabilities, String>),
+    IgnoreVersion,
+    JobEvent(usize, JobEvent),
+    JobMovedUp(usize),
+    JobMovedDown(usize),
+    JobCancelled(usize),
+    JobRemoved(usize),
+    JobSelected(usize),
+    QueuePauseToggled,
+    ClearFinished,
+    MaxJobsChanged(String),
+    SelectInputFiles,
+    InputFilesOpened(Result<Vec<String>, String>),
+    SelectInputFolder,
+    InputFolderOpened(Result<String, String>),
+    ClearBatch,
+    SelectBatchFolder,
+    BatchFolderOpened(Result<String, String>),
+    BatchTemplateChanged(String),
+    Chain,
+    Custom,
+    CustomInputOptionsChanged(String),
+    CustomOutputOptionsChanged(String),
+    CustomFilterChanged(String),
+    ChainStepAdded(StepKind),
+    ChainStepRemoved(usize),
+    ChainStepMovedUp(usize),
+    ChainStepMovedDown(usize),
+    ChainFieldChanged(usize, usize, String),
+    ChainTurnSelected(usize, Turn),
+    ChainPositionSelected(usize, Position),
+    PresetSelected(SavedPreset),
+    PresetNameChanged(String),
+    SavePreset,
+    DeletePreset,
+    ImportPreset,
+    PresetImportOpened(Result<String, String>),
+    ExportPreset,
+    PresetExportPathChosen(Result<String, String>),
+    ToastDetailsToggled(usize),
+    OverwriteChosen,
+    AutoRenameChosen,
+    OverwriteCancelled,
+    LogLevelSelected(LogLevel),
+    LogSearchChanged(String),
+    LogAutoScrollToggled(bool),
+    SaveLog,
+    LogPathChosen(Result<String, String>),
+    CommandPreviewToggled(bool),
+    RefreshCommand,
+    CopyCommand,
+    CommandEditToggled(bool),
+    CommandEdited(text_editor::Action),
+    CommandRecorded(Box<(String, String, Preset)>, Vec<Vec<String>>, Result<(), String>),
 }
 
 impl Controller {
@@ -98,6 +381,486 @@ impl Controller {
         self.can_rotate
     }
 
+    fn toast(&mut self, kind: &str, message: impl Into<String>) {
+        self.toasts.push(HashMap::from([
+            ("message".to_string(), message.into()),
+            ("type".to_string(), kind.to_string()),
+        ]));
+    }
+
+    // Error toast with the full ffmpeg log behind its "Details" button
+    fn toast_failure(&mut self, error: FfmpegError) {
+        let mut toast = HashMap::from([
+            ("message".to_string(), error.message),
+            ("type".to_string(), "error".to_string()),
+        ]);
+        if !error.details.is_empty() {
+            toast.insert("details".to_string(), error.details);
+        }
+        self.toasts.push(toast);
+    }
+
+    // What the stream manager leaves out of the source, shown under the track list
+    fn dropped_streams(&self) -> Option<String> {
+        let probe = self.probe.as_ref()?;
+        let attachments = probe.streams_of("attachment").len();
+        let data = probe.streams_of("data").len();
+        let mut dropped = vec![];
+        if attachments > 0 && !keeps_attachments(&self.dest) {
+            dropped.push(format!("{} attachments (only MKV keeps them)", attachments));
+        }
+        if data > 0 {
+            dropped.push(format!("{} data streams", data));
+        }
+        (!dropped.is_empty()).then(|| format!("Removed from the output: {}", dropped.join(", ")))
+    }
+
+    // Extension the output has to carry for the current action, if it is fixed
+    fn dest_extension(&self) -> Option<&'static str> {
+        operations::output_extension(&self.preset(""), &self.inputs())
+    }
+
+    fn fix_dest_extension(&mut self) {
+        let Some(ext) = self.dest_extension() else {
+            return;
+        };
+        if self.dest == "-" {
+            return;
+        }
+        self.dest = Path::new(&self.dest).with_extension(ext).to_string_lossy().to_string();
+    }
+
+    fn set_loudness_preset(&mut self, preset: LoudnessPreset) {
+        self.norm_preset = preset;
+        if let Some(target) = preset.target() {
+            self.norm_integrated = target.integrated.to_string();
+            self.norm_true_peak = target.true_peak.to_string();
+            self.norm_range = target.range.to_string();
+        }
+    }
+
+    // First audio stream of the probed input
+    fn source_audio(&self) -> Option<&Stream> {
+        self.probe.as_ref().and_then(|p| p.audio_streams().first().copied())
+    }
+
+    // (input, threshold, minimum duration) the silence detection runs with
+    fn silence_settings(&self) -> Option<(String, f64, f64)> {
+        let threshold = self.silence_threshold.trim().parse::<f64>().ok()?;
+        let min_duration = self.silence_min.trim().parse::<f64>().ok()?;
+        Some((self.source.clone(), threshold, min_duration))
+    }
+
+    // Which streams the current action re-encodes, (video, audio)
+    fn encodes(&self) -> (bool, bool) {
+        operations::encodes(&self.preset(""), &self.inputs())
+    }
+
+    // The files the operations read, as picked in the window
+    fn inputs(&self) -> Inputs {
+        Inputs {
+            source: self.source.clone(),
+            probe: self.probe.clone(),
+            extract_stream: self.extract_stream.clone(),
+            slide_images: self.slide_images.clone(),
+            slide_audio: (self.slide_audio != "-").then(|| self.slide_audio.clone()),
+            silences: self.silences.clone(),
+            track_inputs: self.track_inputs.clone(),
+            tracks: self.tracks.clone(),
+        }
+    }
+
+    fn settings(&self) -> settings::Settings {
+        settings::Settings {
+            binaries: Binaries {
+                ffmpeg: self.ffmpeg_path.trim().to_string(),
+                ffprobe: self.ffprobe_path.trim().to_string(),
+            },
+            max_jobs: self.max_jobs.trim().parse::<usize>().unwrap_or(1).max(1),
+        }
+    }
+
+    // Points the engine at the configured binaries and reads what they support
+    // off the UI thread, starting the binaries can take a while. `saved` announces
+    // the version once it is known
+    fn check_ffmpeg(&mut self, saved: bool) -> Task<Message> {
+        let binaries = self.settings().binaries;
+        set_binaries(binaries.clone());
+        in_background(move || {
+            let result = detect().map_err(|e| e.to_string());
+            Message::FfmpegChecked(binaries, saved, result)
+        })
+    }
+
+    fn ffmpeg_checked(&mut self, result: Result<Capabilities, String>) {
+        match result {
+            Ok(capabilities) => {
+                self.ffmpeg_status = if capabilities.is_supported() {
+                    FfmpegStatus::Ready
+                } else {
+                    FfmpegStatus::Outdated(format!(
+                        "ffmpeg {} is installed, but version {}.{} or newer is required. Some operations will fail.",
+                        capabilities.version, MIN_VERSION.0, MIN_VERSION.1
+                    ))
+                };
+                self.capabilities = Some(capabilities);
+            }
+            Err(e) => {
+                self.capabilities = None;
+                self.ffmpeg_status = FfmpegStatus::Missing(format!(
+                    "ffmpeg couldn't be started: {}. Install ffmpeg or point to the ffmpeg and ffprobe executables below.",
+                    e
+                ));
+            }
+        }
+    }
+
+    // What the current action needs from the ffmpeg build
+    fn requirements(&self) -> Requirements {
+        operations::requirements(&self.preset(""), &self.inputs())
+    }
+
+    // Everything the current action needs but the ffmpeg build lacks, empty when unknown
+    fn missing_capabilities(&self) -> Vec<String> {
+        match &self.capabilities {
+            Some(capabilities) => capabilities.missing(&self.requirements()),
+            None => vec![],
+        }
+    }
+
+    // Validates the controls of the current action and captures them in an operation,
+    // toasts and returns None when a value is invalid
+    fn operation(&mut self) -> Option<Operation> {
+        match operations::operation(&self.preset(""), &self.inputs()) {
+            Ok(operation) => Some(operation),
+            Err(e) => {
+                self.toast("error", e);
+                None
+            }
+        }
+    }
+
+    fn output_taken(&self, output: &str) -> bool {
+        operations::output_taken(&self.action, output)
+    }
+
+    fn enqueue_all(&mut self, targets: Vec<(String, String)>, operation: Operation) -> Task<Message> {
+        for (input, output) in targets {
+            if self.action == "frames" {
+                if let Err(e) = fs::create_dir_all(&output) {
+                    self.toast("error", format!("Unable to create {}: {}", output, e));
+                    continue;
+                }
+            }
+            self.enqueue(input, output, operation.clone());
+        }
+        self.schedule()
+    }
+
+    fn enqueue(&mut self, input: String, output: String, operation: Operation) {
+        self.next_job_id += 1;
+        let name = Path::new(&input).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or(input.clone());
+        let label = format!("{} {}", self.action, name);
+        self.jobs.push(Job::new(self.next_job_id, label, input, output, operation));
+        self.update_progress();
+    }
+
+    // Starts pending jobs, in queue order, until the concurrency limit is reached
+    fn schedule(&mut self) -> Task<Message> {
+        if self.queue_paused {
+            return Task::none();
+        }
+        let max_jobs = self.max_jobs.trim().parse::<usize>().unwrap_or(1).max(1);
+        let running = self.jobs.iter().filter(|job| job.status == JobStatus::Running).count();
+        let tasks: Vec<Task<Message>> = self
+            .jobs
+            .iter_mut()
+            .filter(|job| job.status == JobStatus::Pending)
+            .take(max_jobs.saturating_sub(running))
+            .map(|job| {
+                let id = job.id;
+                job.start().map(move |event| Message::JobEvent(id, event))
+            })
+            .collect();
+        Task::batch(tasks)
+    }
+
+    // The progress bar shows the whole queue, cancelled jobs don't count
+    fn update_progress(&mut self) {
+        let jobs: Vec<&Job> = self.jobs.iter().filter(|job| job.status != JobStatus::Cancelled).collect();
+        self.progress = if jobs.is_empty() {
+            0.0
+        } else {
+            jobs.iter().map(|job| if job.status.is_finished() { 100.0 } else { job.progress }).sum::<f32>() / jobs.len() as f32
+        };
+    }
+
+    fn job_position(&self, id: usize) -> Option<usize> {
+        self.jobs.iter().position(|job| job.id == id)
+    }
+
+    // Snapshot of every operation control, saved as a preset named `name`
+    fn preset(&self, name: &str) -> Preset {
+        Preset {
+            name: name.to_string(),
+            action: self.action.clone(),
+            speed_factor: self.speed_factor.clone(),
+            speed_drop_audio: self.speed_drop_audio,
+            anim_format: self.anim_format,
+            anim_fps: self.anim_fps.clone(),
+            anim_width: self.anim_width.clone(),
+            anim_dither: self.anim_dither,
+            anim_loop: self.anim_loop.clone(),
+            anim_start: self.anim_start.clone(),
+            anim_end: self.anim_end.clone(),
+            frames_mode: self.frames_mode,
+            frames_format: self.frames_format,
+            frames_interval: self.frames_interval.clone(),
+            frames_timestamp: self.frames_timestamp.clone(),
+            frames_pattern: self.frames_pattern.clone(),
+            slide_mode: self.slide_mode,
+            slide_fps: self.slide_fps.clone(),
+            slide_size: self.slide_size.clone(),
+            slide_duration: self.slide_duration.clone(),
+            slide_crossfade: self.slide_crossfade.clone(),
+            slide_ken_burns: self.slide_ken_burns,
+            extract_target: self.extract_target,
+            extract_bitrate: self.extract_bitrate.clone(),
+            norm_preset: self.norm_preset,
+            norm_integrated: self.norm_integrated.clone(),
+            norm_true_peak: self.norm_true_peak.clone(),
+            norm_range: self.norm_range.clone(),
+            adjust_gain: self.adjust_gain.clone(),
+            adjust_fade_in: self.adjust_fade_in.clone(),
+            adjust_fade_out: self.adjust_fade_out.clone(),
+            adjust_offset: self.adjust_offset.clone(),
+            silence_threshold: self.silence_threshold.clone(),
+            silence_min: self.silence_min.clone(),
+            silence_padding: self.silence_padding.clone(),
+            channel_mapping: self.channel_mapping,
+            container_format: self.container_format,
+            enc_video_codec: self.enc_video_codec,
+            enc_preset: self.enc_preset.clone(),
+            enc_rate_mode: self.enc_rate_mode,
+            enc_quality: self.enc_quality.clone(),
+            enc_pixel_format: self.enc_pixel_format.clone(),
+            enc_profile: self.enc_profile.clone(),
+            enc_level: self.enc_level.clone(),
+            enc_audio_codec: self.enc_audio_codec,
+            enc_audio_bitrate: self.enc_audio_bitrate.clone(),
+            batch_template: self.batch_template.clone(),
+            chain_steps: self.chain_steps.clone(),
+            custom_input_options: self.custom_input_options.clone(),
+            custom_output_options: self.custom_output_options.clone(),
+            custom_filter: self.custom_filter.clone(),
+        }
+    }
+
+    // The saved preset named in the preset name field
+    fn selected_preset(&self) -> Option<SavedPreset> {
+        self.saved_presets.iter().find(|saved| saved.name == self.preset_name.trim()).cloned()
+    }
+
+    fn apply_preset(&mut self, preset: Preset) {
+        if let Some(message) = action_message(&preset.action) {
+            let _ = self.update(message);
+        }
+        self.preset_name = preset.name;
+        self.speed_factor = preset.speed_factor;
+        self.speed_drop_audio = preset.speed_drop_audio;
+        self.anim_format = preset.anim_format;
+        self.anim_fps = preset.anim_fps;
+        self.anim_width = preset.anim_width;
+        self.anim_dither = preset.anim_dither;
+        self.anim_loop = preset.anim_loop;
+        self.anim_start = preset.anim_start;
+        self.anim_end = preset.anim_end;
+        self.frames_mode = preset.frames_mode;
+        self.frames_format = preset.frames_format;
+        self.frames_interval = preset.frames_interval;
+        self.frames_timestamp = preset.frames_timestamp;
+        self.frames_pattern = preset.frames_pattern;
+        self.slide_mode = preset.slide_mode;
+        self.slide_fps = preset.slide_fps;
+        self.slide_size = preset.slide_size;
+        self.slide_duration = preset.slide_duration;
+        self.slide_crossfade = preset.slide_crossfade;
+        self.slide_ken_burns = preset.slide_ken_burns;
+        self.extract_target = preset.extract_target;
+        self.extract_bitrate = preset.extract_bitrate;
+        self.norm_preset = preset.norm_preset;
+        self.norm_integrated = preset.norm_integrated;
+        self.norm_true_peak = preset.norm_true_peak;
+        self.norm_range = preset.norm_range;
+        self.adjust_gain = preset.adjust_gain;
+        self.adjust_fade_in = preset.adjust_fade_in;
+        self.adjust_fade_out = preset.adjust_fade_out;
+        self.adjust_offset = preset.adjust_offset;
+        self.silence_threshold = preset.silence_threshold;
+        self.silence_min = preset.silence_min;
+        self.silence_padding = preset.silence_padding;
+        self.channel_mapping = preset.channel_mapping;
+        self.container_format = preset.container_format;
+        self.enc_video_codec = preset.enc_video_codec;
+        self.enc_preset = preset.enc_preset;
+        self.enc_rate_mode = preset.enc_rate_mode;
+        self.enc_quality = preset.enc_quality;
+        self.enc_pixel_format = preset.enc_pixel_format;
+        self.enc_profile = preset.enc_profile;
+        self.enc_level = preset.enc_level;
+        self.enc_audio_codec = preset.enc_audio_codec;
+        self.enc_audio_bitrate = preset.enc_audio_bitrate;
+        self.batch_template = preset.batch_template;
+        self.chain_steps = preset.chain_steps;
+        self.custom_input_options = preset.custom_input_options;
+        self.custom_output_options = preset.custom_output_options;
+        self.custom_filter = preset.custom_filter;
+        self.fix_dest_extension();
+    }
+
+    // Fills the command preview with the ffmpeg calls "Do it" would make for the first job.
+    // Building them can run ffprobe, so it is recorded in the background.
+    fn preview_command(&mut self) -> Task<Message> {
+        self.toasts = vec![];
+        self.command_for = None;
+        self.command_note = None;
+        let Some(operation) = self.operation() else {
+            self.command = text_editor::Content::new();
+            return Task::none();
+        };
+        let (input, output) = self.targets().swap_remove(0);
+        let settings = (input, output, self.preset(""));
+        self.command_note = Some("Reading the inputs...".to_string());
+        in_background(move || {
+            let (input, output) = (&settings.0, &settings.1);
+            let (commands, result) = command::record(|| operation(input, output));
+            Message::CommandRecorded(Box::new(settings), commands, result.map(|_| ()).map_err(|e| e.to_string()))
+        })
+    }
+
+    fn command_recorded(&mut self, settings: Box<(String, String, Preset)>, commands: Vec<Vec<String>>, result: Result<(), String>) {
+        // the files or settings changed while it was recording, a newer preview is on its way
+        let (input, output) = self.targets().swap_remove(0);
+        if (&input, &output, &self.preset("")) != (&settings.0, &settings.1, &settings.2) {
+            return;
+        }
+        let lines: Vec<String> = commands.iter().map(|c| command::quote(c)).collect();
+        self.command = text_editor::Content::with_text(&lines.join("\n"));
+        self.command_for = Some(*settings);
+        self.command_note = match result {
+            Err(e) if commands.is_empty() => Some(e),
+            Err(_) => Some("Later steps depend on what ffmpeg reports, their commands are only known while running.".to_string()),
+            Ok(_) if self.is_batch() => Some(format!("Shown for the first of {} files.", self.batch_inputs.len())),
+            Ok(_) => None,
+        };
+    }
+
+    // The edited preview, one command line per non-empty line
+    fn edited_commands(&self) -> Result<Vec<Vec<String>>, String> {
+        let current = self.targets().swap_remove(0);
+        let up_to_date = self.command_for.as_ref().is_some_and(|(input, output, settings)| {
+            (input, output) == (&current.0, &current.1) && *settings == self.preset("")
+        });
+        if !up_to_date {
+            return Err("The files or settings changed since the command preview, refresh it first".to_string());
+        }
+        self.command
+            .text()
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| command::split(line).map_err(|e| format!("Command line {}: {}", i + 1, e)))
+            .collect()
+    }
+
+    fn chain(&self) -> Result<Vec<Step>, String> {
+        operations::chain(&self.preset(""), self.probe.as_ref())
+    }
+
+    // Probes a new input and resets everything derived from the previous one
+    fn set_source(&mut self, file_path: String) {
+        self.source = file_path;
+        self.silences = vec![];
+        match Inputs::probed(&self.source) {
+            Ok(inputs) => {
+                self.extract_stream = inputs.extract_stream;
+                self.track_inputs = inputs.track_inputs;
+                self.tracks = inputs.tracks;
+                self.probe = inputs.probe;
+            }
+            Err(e) => {
+                eprintln!("Error probing file: {}", e);
+                self.extract_stream = None;
+                self.track_inputs = vec![];
+                self.tracks = vec![];
+                self.probe = None;
+            }
+        }
+        self.fix_dest_extension();
+    }
+
+    // The first file stands in for the batch in every control that reads the input
+    fn set_batch_inputs(&mut self, files: Vec<String>) {
+        if let Some(first) = files.first() {
+            self.set_source(first.clone());
+        }
+        self.batch_inputs = files;
+    }
+
+    // (input, output) of every job "Do it" starts, slideshows get a label as input
+    fn targets(&self) -> Vec<(String, String)> {
+        if self.is_batch() {
+            let inputs = &self.batch_inputs;
+            inputs.iter().enumerate().map(|(index, input)| (input.clone(), self.batch_output(input, index))).collect()
+        } else if self.action == "slideshow" {
+            vec![(format!("{} images", self.slide_images.len()), self.dest.clone())]
+        } else {
+            vec![(self.source.clone(), self.dest.clone())]
+        }
+    }
+
+    fn is_batch(&self) -> bool {
+        !self.batch_inputs.is_empty() && !operations::combines_inputs(&self.action)
+    }
+
+    fn batch_output(&self, input: &str, index: usize) -> String {
+        operations::batch_output(&self.preset(""), &self.inputs(), &self.batch_dir, input, index)
+    }
+
+    // Pushes an error toast for every missing path, returns false if any is missing
+    fn check_paths(&mut self) -> bool {
+        let mut ok = true;
+        if self.is_batch() {
+            if !Path::new(&self.batch_dir).is_dir() {
+                self.toast("error", "Choose an output folder for the batch");
+                ok = false;
+            }
+            if self.batch_template.trim().is_empty() {
+                self.toast("error", "Invalid filename template");
+                ok = false;
+            }
+            return ok;
+        }
+        if self.action == "slideshow" {
+            if self.slide_images.is_empty() {
+                self.toast("error", "No images selected");
+                ok = false;
+            }
+        } else if self.source == "-" {
+            self.toast("error", "Invalid input");
+            ok = false;
+        }
+        // analysis only reads, frames are the only action writing into a folder
+        if self.action == "analyze" {
+            return ok;
+        }
+        if self.dest == "-" || (self.action != "frames" && Path::new(&self.dest).is_dir()) {
+            self.toast("error", "Invalid output");
+            ok = false;
+        }
+        ok
+    }
+
     fn update(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::Start => {
@@ -107,10 +870,40 @@ impl Controller {
                 self.image_input = "-".to_string();
                 self.can_image = false;
                 self.can_rotate = false;
-                self.action = "mute".to_string();
                 self.toasts = vec![];
                 self.selected_rot = Some(Rotation::CC90VF);
-                Task::none()
+                self.slide_images = vec![];
+                self.slide_audio = "-".to_string();
+                self.probe = None;
+                self.extract_stream = None;
+                self.loudness_report = None;
+                self.silences = vec![];
+                self.detecting_silence = false;
+                self.track_inputs = vec![];
+                self.tracks = vec![];
+                let settings = settings::load();
+                self.ffmpeg_path = settings.binaries.ffmpeg;
+                self.ffprobe_path = settings.binaries.ffprobe;
+                self.max_jobs = settings.max_jobs.to_string();
+                self.jobs = vec![];
+                self.selected_job = None;
+                self.log_level = LogLevel::Info;
+                self.log_search = "".to_string();
+                self.log_autoscroll = true;
+                self.queue_paused = false;
+                self.batch_inputs = vec![];
+                self.batch_dir = "-".to_string();
+                self.saved_presets = presets::saved();
+                self.preset_name = "".to_string();
+                self.pending_outputs = None;
+                self.command_preview = false;
+                self.command_editable = false;
+                self.command = text_editor::Content::new();
+                self.command_for = None;
+                self.command_note = None;
+                self.apply_preset(Preset::initial());
+                self.ffmpeg_status = FfmpegStatus::Checking;
+                self.check_ffmpeg(false)
             }
             Message::RotateSelected(rot) => {
                 self.selected_rot = Some(rot);
@@ -149,187 +942,1412 @@ impl Controller {
                 self.can_image = true;
                 Task::none()
             }
-            Message::DoIt => {
-                self.toasts = vec![];
-                let mut _is_err = false;
-
-                if self.action == "mute" {
-                    println!("Mute");
-                    if self.source == "-" {
-                        self.toasts.push(HashMap::from([
-                            ("message".to_string(), "Invalid input".to_string()),
-                            ("type".to_string(), "error".to_string()),
-                        ]));
-                        _is_err = true;
-                    }
-                    if self.dest == "-" {
-                        self.toasts.push(HashMap::from([
-                            ("message".to_string(), "Invalid output".to_string()),
-                            ("type".to_string(), "error".to_string()),
-                        ]));
-                        _is_err = true;
-                    }
-                    if _is_err {
-                        return  Task::none();
-                    }
-                    match mute(&self.source, &self.dest) {
-                        Ok(_) => {
-                            self.progress = 100.0;
-                            self.toasts.push(HashMap::from([
-                                ("message".to_string(), "Successfully muted the audio.".to_string()),
-                                ("type".to_string(), "success".to_string()),
-                            ]));
-                        }
-                        Err(e) => {
-                            self.toasts.push(HashMap::from([
-                                (e.to_string(), "Invalid output".to_string()),
-                                ("type".to_string(), "error".to_string()),
-                            ]));
-                            self.progress = 0.0;
-                        }
-                    }
+            Message::Speed => {
+                self.action = "speed".to_string();
+                self.can_image = false;
+                self.can_rotate = false;
+                Task::none()
+            }
+            Message::SpeedChanged(value) => {
+                self.speed_factor = value;
+                Task::none()
+            }
+            Message::SpeedPresetSelected(preset) => {
+                self.speed_factor = preset.0.to_string();
+                Task::none()
+            }
+            Message::SpeedDropAudioToggled(drop_audio) => {
+                self.speed_drop_audio = drop_audio;
+                Task::none()
+            }
+            Message::Animation => {
+                self.action = "animation".to_string();
+                self.can_image = false;
+                self.can_rotate = false;
+                self.fix_dest_extension();
+                Task::none()
+            }
+            Message::AnimationFormatSelected(format) => {
+                self.anim_format = format;
+                self.fix_dest_extension();
+                Task::none()
+            }
+            Message::AnimationFpsChanged(value) => {
+                self.anim_fps = value;
+                Task::none()
+            }
+            Message::AnimationWidthChanged(value) => {
+                self.anim_width = value;
+                Task::none()
+            }
+            Message::AnimationDitherSelected(dither) => {
+                self.anim_dither = dither;
+                Task::none()
+            }
+            Message::AnimationLoopChanged(value) => {
+                self.anim_loop = value;
+                Task::none()
+            }
+            Message::AnimationStartChanged(value) => {
+                self.anim_start = value;
+                Task::none()
+            }
+            Message::AnimationEndChanged(value) => {
+                self.anim_end = value;
+                Task::none()
+            }
+            Message::Frames => {
+                self.action = "frames".to_string();
+                self.can_image = false;
+                self.can_rotate = false;
+                if !Path::new(&self.dest).is_dir() {
+                    self.dest = "-".to_string();
                 }
-                // rotate
-                if self.action == "rotate" {
-                    println!("Rotate");
-                    if self.source == "-" {
-                        self.toasts.push(HashMap::from([
-                            ("message".to_string(), "Invalid input".to_string()),
-                            ("type".to_string(), "error".to_string()),
-                        ]));
-                        _is_err = true;
-                    }
-                    if self.dest == "-" {
-                        self.toasts.push(HashMap::from([
-                            ("message".to_string(), "Invalid output".to_string()),
-                            ("type".to_string(), "error".to_string()),
-                        ]));
-                        _is_err = true;
-                    }
-                    if _is_err {
-                        return  Task::none();
+                Task::none()
+            }
+            Message::SelectOutputFolder => {
+                Task::perform(open_folder(), Message::OutputFolderOpened)
+            }
+            Message::OutputFolderOpened(result) => {
+                match result {
+                    Ok(folder) => {
+                        self.dest = folder;
                     }
-                    match rotate(&self.source, &self.dest,"transpose=1" ) {
-                        Ok(_) => {
-                            self.progress = 100.0;
-                            self.toasts.push(HashMap::from([
-                                ("message".to_string(), "Successfully rotated the video.".to_string()),
-                                ("type".to_string(), "success".to_string()),
-                            ]));
-                        }
-                        Err(e) => {
-                            self.toasts.push(HashMap::from([
-                                (e.to_string(), "Invalid output".to_string()),
-                                ("type".to_string(), "error".to_string()),
-                            ]));
-                            self.progress = 0.0;
-                        }
+                    Err(e) => {
+                        eprintln!("Error selecting folder: {}", e);
                     }
                 }
+
                 Task::none()
             }
-
-            Message::SelectInputVideo => {
-                println!("Select input video");
-                // tokio::spawn(async {
-                //     match open_file().await {
-                //         Ok(file_path) => {
-                //             print!("{}", file_path);
-                //         }
-                //         Err(e) => {
-                //             eprintln!("Error selecting file: {}", e); // Handle the error (optional)
-                //             // Do nothing if the file selection failed
-                //         }
-                //     }
-                // });
-
-                Task::perform(open_file(&["mp4", "mkv"]), Message::InputVideoOpened)
+            Message::FrameModeSelected(mode) => {
+                self.frames_mode = mode;
+                Task::none()
             }
-            Message::InputVideoOpened(result) => {
+            Message::FrameFormatSelected(format) => {
+                self.frames_format = format;
+                Task::none()
+            }
+            Message::FrameIntervalChanged(value) => {
+                self.frames_interval = value;
+                Task::none()
+            }
+            Message::FrameTimestampChanged(value) => {
+                self.frames_timestamp = value;
+                Task::none()
+            }
+            Message::FramePatternChanged(value) => {
+                self.frames_pattern = value;
+                Task::none()
+            }
+            Message::Slideshow => {
+                self.action = "slideshow".to_string();
+                self.can_image = false;
+                self.can_rotate = false;
+                Task::none()
+            }
+            Message::SelectImages => {
+                Task::perform(open_files(&["png", "jpg", "jpeg", "webp", "bmp"]), Message::ImagesOpened)
+            }
+            Message::ImagesOpened(result) => {
                 match result {
-                    Ok(file_path) => {
-                        self.source = file_path;
+                    Ok(files) => {
+                        self.slide_images = files;
                     }
                     Err(e) => {
-                        eprintln!("Error selecting file: {}", e); // Handle the error (optional)
-                                                                  // Do nothing if the file selection failed
+                        eprintln!("Error selecting files: {}", e);
                     }
                 }
 
                 Task::none()
             }
-            Message::SelectImage => {
-                println!("Select image");
+            Message::SelectImageFolder => {
+                Task::perform(open_folder(), Message::ImageFolderOpened)
+            }
+            Message::ImageFolderOpened(result) => {
+                match result {
+                    Ok(folder) => match list_images(&folder) {
+                        Ok(images) => {
+                            self.slide_images = images;
+                        }
+                        Err(e) => {
+                            self.toast("error", e.to_string());
+                        }
+                    },
+                    Err(e) => {
+                        eprintln!("Error selecting folder: {}", e);
+                    }
+                }
 
-                Task::perform(open_file(&["png", "jpg"]), Message::ImageOpened)
+                Task::none()
             }
-            Message::ImageOpened(result) => {
+            Message::SlideshowModeSelected(mode) => {
+                self.slide_mode = mode;
+                Task::none()
+            }
+            Message::SlideshowFpsChanged(value) => {
+                self.slide_fps = value;
+                Task::none()
+            }
+            Message::SlideshowSizeChanged(value) => {
+                self.slide_size = value;
+                Task::none()
+            }
+            Message::SlideshowDurationChanged(value) => {
+                self.slide_duration = value;
+                Task::none()
+            }
+            Message::SlideshowCrossfadeChanged(value) => {
+                self.slide_crossfade = value;
+                Task::none()
+            }
+            Message::SlideshowKenBurnsToggled(ken_burns) => {
+                self.slide_ken_burns = ken_burns;
+                Task::none()
+            }
+            Message::SelectSlideshowAudio => {
+                Task::perform(open_file(&["mp3", "m4a", "aac", "wav", "flac", "ogg", "opus"]), Message::SlideshowAudioOpened)
+            }
+            Message::SlideshowAudioOpened(result) => {
                 match result {
                     Ok(file_path) => {
-                        self.image_input = file_path;
+                        self.slide_audio = file_path;
                     }
                     Err(e) => {
-                        eprintln!("Error selecting file: {}", e); // Handle the error (optional)
-                                                                  // Do nothing if the file selection failed
+                        eprintln!("Error selecting file: {}", e);
                     }
                 }
 
                 Task::none()
             }
-            Message::SelectOutputVideo => {
-                println!("Select out video");
+            Message::ExtractAudio => {
+                self.action = "extract_audio".to_string();
+                self.can_image = false;
+                self.can_rotate = false;
+                self.fix_dest_extension();
+                Task::none()
+            }
+            Message::ExtractStreamSelected(stream) => {
+                self.extract_stream = Some(*stream);
+                self.fix_dest_extension();
+                Task::none()
+            }
+            Message::ExtractTargetSelected(target) => {
+                self.extract_target = target;
+                self.fix_dest_extension();
+                Task::none()
+            }
+            Message::ExtractBitrateChanged(value) => {
+                self.extract_bitrate = value;
+                Task::none()
+            }
+            Message::Normalize => {
+                self.action = "normalize".to_string();
+                self.can_image = false;
+                self.can_rotate = false;
+                Task::none()
+            }
+            Message::NormalizePresetSelected(preset) => {
+                self.set_loudness_preset(preset);
+                Task::none()
+            }
+            Message::NormalizeIntegratedChanged(value) => {
+                self.norm_integrated = value;
+                self.norm_preset = LoudnessPreset::Custom;
+                Task::none()
+            }
+            Message::NormalizeTruePeakChanged(value) => {
+                self.norm_true_peak = value;
+                self.norm_preset = LoudnessPreset::Custom;
+                Task::none()
+            }
+            Message::NormalizeRangeChanged(value) => {
+                self.norm_range = value;
+                self.norm_preset = LoudnessPreset::Custom;
+                Task::none()
+            }
+            Message::Analyze => {
+                self.action = "analyze".to_string();
+                self.can_image = false;
+                self.can_rotate = false;
+                Task::none()
+            }
+            Message::ExportReport => {
+                Task::perform(save_file(&["json"]), Message::ReportPathChosen)
+            }
+            Message::ReportPathChosen(result) => {
+                let (Ok(file_path), Some(report)) = (result, &self.loudness_report) else {
+                    return Task::none();
+                };
+                let file_path = Path::new(&file_path).with_extension("json");
+                match report.to_json().and_then(|json| fs::write(&file_path, json)) {
+                    Ok(_) => self.toast("success", format!("Report saved to {}", file_path.display())),
+                    Err(e) => self.toast("error", e.to_string()),
+                }
+                Task::none()
+            }
+            Message::AdjustAudio => {
+                self.action = "adjust_audio".to_string();
+                self.can_image = false;
+                self.can_rotate = false;
+                Task::none()
+            }
+            Message::AdjustGainChanged(value) => {
+                self.adjust_gain = value;
+                Task::none()
+            }
+            Message::AdjustFadeInChanged(value) => {
+                self.adjust_fade_in = value;
+                Task::none()
+            }
+            Message::AdjustFadeOutChanged(value) => {
+                self.adjust_fade_out = value;
+                Task::none()
+            }
+            Message::AdjustOffsetChanged(value) => {
+                self.adjust_offset = value;
+                Task::none()
+            }
+            Message::RemoveSilence => {
+                self.action = "remove_silence".to_string();
+                self.can_image = false;
+                self.can_rotate = false;
+                Task::none()
+            }
+            Message::SilenceThresholdChanged(value) => {
+                self.silence_threshold = value;
+                self.silences = vec![];
+                Task::none()
+            }
+            Message::SilenceMinChanged(value) => {
+                self.silence_min = value;
+                self.silences = vec![];
+                Task::none()
+            }
+            Message::SilencePaddingChanged(value) => {
+                self.silence_padding = value;
+                Task::none()
+            }
+            Message::DetectSilence => {
+                self.toasts = vec![];
+                if self.source == "-" {
+                    self.toast("error", "Invalid input");
+                    return Task::none();
+                }
+                let Some((source, threshold, min_duration)) = self.silence_settings() else {
+                    self.toast("error", "Invalid threshold or minimum duration");
+                    return Task::none();
+                };
+                self.detecting_silence = true;
+                in_background(move || {
+                    let result = detect_silence(&source, threshold, min_duration).map_err(|e| FfmpegError::from_io(&e));
+                    Message::SilenceDetected((source, threshold, min_duration), result)
+                })
+            }
+            Message::SilenceDetected(settings, result) => {
+                self.detecting_silence = false;
+                // the input or the thresholds changed while ffmpeg was reading
+                if self.silence_settings() != Some(settings) {
+                    return Task::none();
+                }
+                match result {
+                    Ok(silences) if silences.is_empty() => {
+                        self.silences = silences;
+                        self.toast("error", "No silence found, try a higher threshold");
+                    }
+                    Ok(silences) => self.silences = silences,
+                    Err(error) => {
+                        self.silences = vec![];
+                        self.toast_failure(error);
+                    }
+                }
+                Task::none()
+            }
+            Message::Channels => {
+                self.action = "channels".to_string();
+                self.can_image = false;
+                self.can_rotate = false;
+                if let Some(channels) = self.source_audio().and_then(|s| s.channels) {
+                    self.channel_mapping = ChannelMapping::for_channels(channels);
+                }
+                Task::none()
+            }
+            Message::ChannelMappingSelected(mapping) => {
+                self.channel_mapping = mapping;
+                Task::none()
+            }
+            Message::Convert => {
+                self.action = "convert".to_string();
+                self.can_image = false;
+                self.can_rotate = false;
+                self.fix_dest_extension();
+                Task::none()
+            }
+            Message::ContainerSelected(format) => {
+                self.container_format = format;
+                self.fix_dest_extension();
+                Task::none()
+            }
+            Message::Settings => {
+                self.action = "settings".to_string();
+                self.can_image = false;
+                self.can_rotate = false;
+                Task::none()
+            }
+            Message::FfmpegPathChanged(path) => {
+                self.ffmpeg_path = path;
+                Task::none()
+            }
+            Message::FfprobePathChanged(path) => {
+                self.ffprobe_path = path;
+                Task::none()
+            }
+            Message::BrowseFfmpeg => Task::perform(open_file(&["*"]), Message::FfmpegBinaryOpened),
+            Message::FfmpegBinaryOpened(result) => {
+                if let Ok(path) = result {
+                    // ffprobe ships next to ffmpeg in every distribution
+                    if let Some(probe) = sibling_binary(&path, "ffmpeg", "ffprobe") {
+                        self.ffprobe_path = probe;
+                    }
+                    self.ffmpeg_path = path;
+                }
+                Task::none()
+            }
+            Message::BrowseFfprobe => Task::perform(open_file(&["*"]), Message::FfprobeBinaryOpened),
+            Message::FfprobeBinaryOpened(result) => {
+                if let Ok(path) = result {
+                    self.ffprobe_path = path;
+                }
+                Task::none()
+            }
+            Message::SaveSettings => {
+                self.toasts = vec![];
+                match settings::save(&self.settings()) {
+                    Ok(_) => self.check_ffmpeg(true),
+                    Err(e) => {
+                        self.toast("error", format!("Unable to save the settings: {}", e));
+                        self.check_ffmpeg(false)
+                    }
+                }
+            }
+            Message::FfmpegChecked(binaries, saved, result) => {
+                // the paths changed again while the older binaries were starting
+                if binaries != self.settings().binaries {
+                    return Task::none();
+                }
+                self.ffmpeg_checked(result);
+                if let (true, Some(capabilities)) = (saved, &self.capabilities) {
+                    let message = format!("Settings saved, using ffmpeg {}", capabilities.version);
+                    self.toast("success", message);
+                }
+                Task::none()
+            }
+            Message::IgnoreVersion => {
+                self.ffmpeg_status = FfmpegStatus::Ready;
+                Task::none()
+            }
+            Message::VideoCodecSelected(codec) => {
+                // presets, profiles and the CRF scale are all codec specific
+                self.enc_video_codec = codec;
+                self.enc_preset = None;
+                self.enc_profile = None;
+                if self.enc_rate_mode == RateMode::Crf {
+                    self.enc_quality = if codec.encodes() { codec.default_crf().to_string() } else { "".to_string() };
+                }
+                Task::none()
+            }
+            Message::EncoderPresetSelected(preset) => {
+                self.enc_preset = Some(preset);
+                Task::none()
+            }
+            Message::RateModeSelected(mode) => {
+                self.enc_rate_mode = mode;
+                self.enc_quality = match mode {
+                    RateMode::Crf => self.enc_video_codec.default_crf().to_string(),
+                    RateMode::Bitrate => "4000".to_string(),
+                };
+                Task::none()
+            }
+            Message::QualityChanged(value) => {
+                self.enc_quality = value;
+                Task::none()
+            }
+            Message::PixelFormatChanged(value) => {
+                self.enc_pixel_format = value;
+                Task::none()
+            }
+            Message::ProfileSelected(profile) => {
+                self.enc_profile = Some(profile);
+                Task::none()
+            }
+            Message::LevelChanged(value) => {
+                self.enc_level = value;
+                Task::none()
+            }
+            Message::AudioCodecSelected(codec) => {
+                self.enc_audio_codec = codec;
+                Task::none()
+            }
+            Message::AudioBitrateChanged(value) => {
+                self.enc_audio_bitrate = value;
+                Task::none()
+            }
+            Message::Streams => {
+                self.action = "streams".to_string();
+                self.can_image = false;
+                self.can_rotate = false;
+                Task::none()
+            }
+            Message::TrackToggled(i, enabled) => {
+                if let Some(track) = self.tracks.get_mut(i) {
+                    track.enabled = enabled;
+                }
+                Task::none()
+            }
+            Message::TrackMovedUp(i) => {
+                if i > 0 && i < self.tracks.len() {
+                    self.tracks.swap(i, i - 1);
+                }
+                Task::none()
+            }
+            Message::TrackMovedDown(i) => {
+                if i + 1 < self.tracks.len() {
+                    self.tracks.swap(i, i + 1);
+                }
+                Task::none()
+            }
+            Message::TrackLanguageChanged(i, language) => {
+                if let Some(track) = self.tracks.get_mut(i) {
+                    track.language = language;
+                }
+                Task::none()
+            }
+            Message::TrackTitleChanged(i, title) => {
+                if let Some(track) = self.tracks.get_mut(i) {
+                    track.title = title;
+                }
+                Task::none()
+            }
+            Message::TrackDefaultToggled(i, default) => {
+                // only one default track per kind
+                let kind = self.tracks.get(i).map(|t| t.kind.clone()).unwrap_or_default();
+                for (j, track) in self.tracks.iter_mut().enumerate() {
+                    if track.kind == kind {
+                        track.default = j == i && default;
+                    }
+                }
+                Task::none()
+            }
+            Message::AddTrack => {
+                Task::perform(
+                    open_file(&["m4a", "aac", "mp3", "ac3", "opus", "ogg", "flac", "wav", "mka", "srt", "ass", "vtt"]),
+                    Message::TrackFileOpened,
+                )
+            }
+            Message::TrackFileOpened(result) => {
+                match result {
+                    Ok(file_path) => {
+                        if self.track_inputs.is_empty() {
+                            self.toast("error", "Choose the input video first");
+                            return Task::none();
+                        }
+                        let mut inputs = self.inputs();
+                        match inputs.add_track_input(&file_path) {
+                            Ok(()) => {
+                                self.track_inputs = inputs.track_inputs;
+                                self.tracks = inputs.tracks;
+                            }
+                            Err(e) => self.toast_failure(FfmpegError::from_io(&e)),
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Error selecting file: {}", e);
+                    }
+                }
+
+                Task::none()
+            }
+            Message::DoIt => {
+                self.toasts = vec![];
+                let missing = self.missing_capabilities();
+                if !missing.is_empty() {
+                    self.toast("error", format!("Your ffmpeg build lacks {}", missing.join(", ")));
+                    return Task::none();
+                }
+                if !self.check_paths() {
+                    return Task::none();
+                }
+                let Some(mut operation) = self.operation() else {
+                    return Task::none();
+                };
+                if self.command_preview && self.command_editable && !self.is_batch() {
+                    let commands = match self.edited_commands() {
+                        Ok(commands) => commands,
+                        Err(e) => {
+                            self.toast("error", e);
+                            return Task::none();
+                        }
+                    };
+                    let generated = operation;
+                    let (_, edited_output) = self.targets().swap_remove(0);
+                    operation = Arc::new(move |input, output| {
+                        // "Rename" in the overwrite prompt moves the output away from the edited lines
+                        if output != edited_output {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                format!("The edited command writes to {}, refresh the preview for {}", edited_output, output),
+                            ));
+                        }
+                        command::with_commands(commands.clone(), || generated(input, output))
+                    });
+                }
+                let targets = self.targets();
+                let mut checked = vec![];
+                for (input, output) in targets {
+                    if same_file(&input, &output) {
+                        self.toast("error", format!("Skipped {}, the output would overwrite it", input));
+                        continue;
+                    }
+                    checked.push((input, output));
+                }
+                // existing outputs wait for the user to pick overwrite, rename or cancel
+                if checked.iter().any(|(_, output)| self.output_taken(output)) {
+                    self.pending_outputs = Some((checked, operation));
+                    return Task::none();
+                }
+                self.enqueue_all(checked, operation)
+            }
+            Message::OverwriteChosen => {
+                let Some((targets, operation)) = self.pending_outputs.take() else {
+                    return Task::none();
+                };
+                self.enqueue_all(targets, operation)
+            }
+            Message::AutoRenameChosen => {
+                let Some((targets, operation)) = self.pending_outputs.take() else {
+                    return Task::none();
+                };
+                let targets = targets.into_iter().map(|(input, output)| (input, free_path(&output))).collect();
+                self.enqueue_all(targets, operation)
+            }
+            Message::OverwriteCancelled => {
+                self.pending_outputs = None;
+                Task::none()
+            }
+            Message::JobEvent(id, event) => {
+                let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) else {
+                    return Task::none();
+                };
+                match event {
+                    JobEvent::Progress(progress) => job.progress = progress,
+                    JobEvent::Log(line) => job.push_log(line),
+                    JobEvent::Finished(result) => {
+                        match &result {
+                            Ok(_) => job.push_log("Finished".to_string()),
+                            Err(e) => job.push_log(format!("Failed: {}", e.message)),
+                        }
+                        job.close_log();
+                        let result = match result {
+                            Ok(output) => {
+                                job.status = JobStatus::Done;
+                                job.progress = 100.0;
+                                Ok(output)
+                            }
+                            Err(_) if job.is_cancelled() => {
+                                job.status = JobStatus::Cancelled;
+                                Err(None)
+                            }
+                            Err(e) => {
+                                job.status = JobStatus::Failed;
+                                Err(Some(FfmpegError {
+                                    message: format!("{} failed: {}", job.label, e.message),
+                                    ..e
+                                }))
+                            }
+                        };
+                        match result {
+                            Ok(JobOutput::Message(message)) => self.toast("success", message),
+                            Ok(JobOutput::Report(report)) => {
+                                self.loudness_report = Some(report);
+                                self.toast("success", "Successfully analyzed the audio.");
+                            }
+                            Err(Some(error)) => self.toast_failure(error),
+                            Err(None) => {}
+                        }
+                        self.update_progress();
+                        return self.schedule();
+                    }
+                }
+                self.update_progress();
+                Task::none()
+            }
+            Message::JobMovedUp(id) => {
+                if let Some(i) = self.job_position(id).filter(|i| *i > 0) {
+                    self.jobs.swap(i, i - 1);
+                }
+                Task::none()
+            }
+            Message::JobMovedDown(id) => {
+                if let Some(i) = self.job_position(id).filter(|i| i + 1 < self.jobs.len()) {
+                    self.jobs.swap(i, i + 1);
+                }
+                Task::none()
+            }
+            Message::JobCancelled(id) => {
+                if let Some(i) = self.job_position(id) {
+                    self.jobs[i].cancel();
+                }
+                self.update_progress();
+                Task::none()
+            }
+            Message::JobRemoved(id) => {
+                // running jobs have to be cancelled first, their thread still reports back
+                self.jobs.retain(|job| job.id != id || job.status == JobStatus::Running);
+                if self.selected_job == Some(id) {
+                    self.selected_job = None;
+                }
+                self.update_progress();
+                Task::none()
+            }
+            Message::JobSelected(id) => {
+                self.selected_job = (self.selected_job != Some(id)).then_some(id);
+                Task::none()
+            }
+            Message::LogLevelSelected(level) => {
+                self.log_level = level;
+                Task::none()
+            }
+            Message::LogSearchChanged(search) => {
+                self.log_search = search;
+                Task::none()
+            }
+            Message::LogAutoScrollToggled(autoscroll) => {
+                self.log_autoscroll = autoscroll;
+                Task::none()
+            }
+            Message::SaveLog => Task::perform(save_file(&["log", "txt"]), Message::LogPathChosen),
+            Message::LogPathChosen(result) => {
+                let job = self.selected_job.and_then(|id| self.jobs.iter().find(|job| job.id == id));
+                let (Ok(file_path), Some(job)) = (result, job) else {
+                    return Task::none();
+                };
+                let mut file_path = PathBuf::from(file_path);
+                if file_path.extension().is_none() {
+                    file_path.set_extension("log");
+                }
+                match fs::write(&file_path, job.full_log()) {
+                    Ok(_) => self.toast("success", format!("Log saved to {}", file_path.display())),
+                    Err(e) => self.toast("error", format!("Unable to save the log: {}", e)),
+                }
+                Task::none()
+            }
+            Message::QueuePauseToggled => {
+                self.queue_paused = !self.queue_paused;
+                self.schedule()
+            }
+            Message::ClearFinished => {
+                self.jobs.retain(|job| !job.status.is_finished());
+                if self.selected_job.is_some_and(|id| self.job_position(id).is_none()) {
+                    self.selected_job = None;
+                }
+                self.update_progress();
+                Task::none()
+            }
+            Message::MaxJobsChanged(value) => {
+                self.max_jobs = value;
+                if self.max_jobs.trim().parse::<usize>().is_ok_and(|max| max > 0) {
+                    if let Err(e) = settings::save(&self.settings()) {
+                        eprintln!("Unable to save the settings: {}", e);
+                    }
+                }
+                self.schedule()
+            }
+
+            Message::SelectInputVideo => {
+                println!("Select input video");
+                // tokio::spawn(async {
+                //     match open_file().await {
+                //         Ok(file_path) => {
+                //             print!("{}", file_path);
+                //         }
+                //         Err(e) => {
+                //             eprintln!("Error selecting file: {}", e); // Handle the error (optional)
+                //             // Do nothing if the file selection failed
+                //         }
+                //     }
+                // });
+
+                Task::perform(open_file(&VIDEO_EXTENSIONS), Message::InputVideoOpened)
+            }
+            Message::InputVideoOpened(result) => {
+                match result {
+                    Ok(file_path) => {
+                        self.batch_inputs = vec![];
+                        self.set_source(file_path);
+                    }
+                    Err(e) => {
+                        eprintln!("Error selecting file: {}", e); // Handle the error (optional)
+                                                                  // Do nothing if the file selection failed
+                    }
+                }
+
+                Task::none()
+            }
+            Message::Chain => {
+                self.action = "chain".to_string();
+                self.can_image = false;
+                self.can_rotate = false;
+                Task::none()
+            }
+            Message::Custom => {
+                self.action = "custom".to_string();
+                self.can_image = false;
+                self.can_rotate = false;
+                Task::none()
+            }
+            Message::CustomInputOptionsChanged(value) => {
+                self.custom_input_options = value;
+                Task::none()
+            }
+            Message::CustomOutputOptionsChanged(value) => {
+                self.custom_output_options = value;
+                Task::none()
+            }
+            Message::CustomFilterChanged(value) => {
+                self.custom_filter = value;
+                Task::none()
+            }
+            Message::ChainStepAdded(kind) => {
+                self.chain_steps.push(ChainStep::new(kind));
+                Task::none()
+            }
+            Message::ChainStepRemoved(i) => {
+                if i < self.chain_steps.len() {
+                    self.chain_steps.remove(i);
+                }
+                Task::none()
+            }
+            Message::ChainStepMovedUp(i) => {
+                if i > 0 && i < self.chain_steps.len() {
+                    self.chain_steps.swap(i, i - 1);
+                }
+                Task::none()
+            }
+            Message::ChainStepMovedDown(i) => {
+                if i + 1 < self.chain_steps.len() {
+                    self.chain_steps.swap(i, i + 1);
+                }
+                Task::none()
+            }
+            Message::ChainFieldChanged(i, field, value) => {
+                if let Some(value_ref) = self.chain_steps.get_mut(i).and_then(|step| step.fields.get_mut(field)) {
+                    *value_ref = value;
+                }
+                Task::none()
+            }
+            Message::ChainTurnSelected(i, turn) => {
+                if let Some(step) = self.chain_steps.get_mut(i) {
+                    step.turn = turn;
+                }
+                Task::none()
+            }
+            Message::ChainPositionSelected(i, position) => {
+                if let Some(step) = self.chain_steps.get_mut(i) {
+                    step.position = position;
+                }
+                Task::none()
+            }
+            Message::PresetSelected(saved) => {
+                self.toasts = vec![];
+                match presets::load(&saved, &self.preset(&saved.name)) {
+                    Ok(preset) => self.apply_preset(preset),
+                    Err(e) => self.toast("error", format!("Unable to load the preset: {}", e)),
+                }
+                Task::none()
+            }
+            Message::PresetNameChanged(name) => {
+                self.preset_name = name;
+                Task::none()
+            }
+            Message::SavePreset => {
+                self.toasts = vec![];
+                let preset = self.preset(self.preset_name.trim());
+                match presets::save(&preset) {
+                    Ok(_) => {
+                        self.saved_presets = presets::saved();
+                        self.toast("success", format!("Preset {} saved.", preset.name));
+                    }
+                    Err(e) => self.toast("error", format!("Unable to save the preset: {}", e)),
+                }
+                Task::none()
+            }
+            Message::DeletePreset => {
+                self.toasts = vec![];
+                let Some(saved) = self.selected_preset() else {
+                    return Task::none();
+                };
+                match presets::delete(&saved) {
+                    Ok(_) => {
+                        self.saved_presets = presets::saved();
+                        self.toast("success", format!("Preset {} deleted.", self.preset_name.trim()));
+                    }
+                    Err(e) => self.toast("error", format!("Unable to delete the preset: {}", e)),
+                }
+                Task::none()
+            }
+            Message::ImportPreset => Task::perform(open_file(&["json"]), Message::PresetImportOpened),
+            Message::PresetImportOpened(result) => {
+                let Ok(file_path) = result else {
+                    return Task::none();
+                };
+                self.toasts = vec![];
+                // imported presets are copied next to the others so they show up in the list
+                match presets::read_file(Path::new(&file_path), &self.preset("")).and_then(|preset| {
+                    presets::save(&preset)?;
+                    Ok(preset)
+                }) {
+                    Ok(preset) => {
+                        self.saved_presets = presets::saved();
+                        self.toast("success", format!("Preset {} imported.", preset.name));
+                        self.apply_preset(preset);
+                    }
+                    Err(e) => self.toast("error", format!("Unable to import the preset: {}", e)),
+                }
+                Task::none()
+            }
+            Message::ExportPreset => Task::perform(save_file(&["json"]), Message::PresetExportPathChosen),
+            Message::PresetExportPathChosen(result) => {
+                let Ok(file_path) = result else {
+                    return Task::none();
+                };
+                let file_path = Path::new(&file_path).with_extension("json");
+                let name = file_path.file_stem().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                let name = non_empty(&self.preset_name).unwrap_or(name);
+                match presets::write_file(&file_path, &self.preset(&name)) {
+                    Ok(_) => self.toast("success", format!("Preset exported to {}", file_path.display())),
+                    Err(e) => self.toast("error", e.to_string()),
+                }
+                Task::none()
+            }
+            Message::ToastDetailsToggled(i) => {
+                if let Some(toast) = self.toasts.get_mut(i) {
+                    if toast.remove("expanded").is_none() {
+                        toast.insert("expanded".to_string(), "true".to_string());
+                    }
+                }
+                Task::none()
+            }
+            Message::CommandPreviewToggled(shown) => {
+                self.command_preview = shown;
+                if shown {
+                    return self.preview_command();
+                }
+                Task::none()
+            }
+            Message::RefreshCommand => self.preview_command(),
+            Message::CommandRecorded(settings, commands, result) => {
+                self.command_recorded(settings, commands, result);
+                Task::none()
+            }
+            Message::CopyCommand => iced::clipboard::write(self.command.text()),
+            Message::CommandEditToggled(editable) => {
+                self.command_editable = editable;
+                Task::none()
+            }
+            Message::CommandEdited(action) => {
+                self.command.perform(action);
+                Task::none()
+            }
+            Message::SelectInputFiles => Task::perform(open_files(&VIDEO_EXTENSIONS), Message::InputFilesOpened),
+            Message::InputFilesOpened(result) => {
+                match result {
+                    Ok(files) => self.set_batch_inputs(files),
+                    Err(e) => eprintln!("Error selecting files: {}", e),
+                }
+                Task::none()
+            }
+            Message::SelectInputFolder => Task::perform(open_folder(), Message::InputFolderOpened),
+            Message::InputFolderOpened(result) => {
+                match result {
+                    Ok(folder) => match list_files(&folder, &VIDEO_EXTENSIONS) {
+                        Ok(files) if files.is_empty() => self.toast("error", "No videos found in the folder"),
+                        Ok(files) => self.set_batch_inputs(files),
+                        Err(e) => self.toast("error", e.to_string()),
+                    },
+                    Err(e) => eprintln!("Error selecting folder: {}", e),
+                }
+                Task::none()
+            }
+            Message::ClearBatch => {
+                self.batch_inputs = vec![];
+                Task::none()
+            }
+            Message::SelectBatchFolder => Task::perform(open_folder(), Message::BatchFolderOpened),
+            Message::BatchFolderOpened(result) => {
+                match result {
+                    Ok(folder) => self.batch_dir = folder,
+                    Err(e) => eprintln!("Error selecting folder: {}", e),
+                }
+                Task::none()
+            }
+            Message::BatchTemplateChanged(template) => {
+                self.batch_template = template;
+                Task::none()
+            }
+            Message::SelectImage => {
+                println!("Select image");
+
+                Task::perform(open_file(&["png", "jpg"]), Message::ImageOpened)
+            }
+            Message::ImageOpened(result) => {
+                match result {
+                    Ok(file_path) => {
+                        self.image_input = file_path;
+                    }
+                    Err(e) => {
+                        eprintln!("Error selecting file: {}", e); // Handle the error (optional)
+                                                                  // Do nothing if the file selection failed
+                    }
+                }
+
+                Task::none()
+            }
+            Message::SelectOutputVideo => {
+                println!("Select out video");
+
+                if let Some(ext) = self.dest_extension() {
+                    return Task::perform(async move { save_file(&[ext]).await }, Message::OutputVideoOpened);
+                }
+                Task::perform(save_file(&VIDEO_EXTENSIONS), Message::OutputVideoOpened)
+            }
+            Message::OutputVideoOpened(result) => {
+                match result {
+                    Ok(file_path) if self.dest_extension().is_some() => {
+                        self.dest = file_path;
+                        self.fix_dest_extension();
+                    }
+                    Ok(file_path) => {
+                        // Keep a known video extension, otherwise fall back to ".mp4"
+                        let ext = Path::new(&file_path)
+                            .extension()
+                            .map(|e| e.to_string_lossy().to_lowercase())
+                            .unwrap_or_default();
+                        if !VIDEO_EXTENSIONS.contains(&ext.as_str()) {
+                            // Append ".mp4" to the file_path
+                            self.dest = format!("{}.mp4", file_path);
+                        } else {
+                            self.dest = file_path;
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Error selecting file: {}", e); // Handle the error (optional)
+                                                                  // Do nothing if the file selection failed
+                    }
+                }
+
+                Task::none()
+            },
+        }
+    }
+
+    // Codec panel shared by every action that re-encodes
+    fn encoding_controls(&self) -> Option<Column<'_, Message>> {
+        let (video, audio) = self.encodes();
+        if !video && !audio {
+            return None;
+        }
+        let codec = self.enc_video_codec;
+        let controls = column![].spacing(7).push_maybe(video.then(|| {
+            row![
+                text("Video codec: ").width(200),
+                pick_list(VideoCodec::ALL, Some(codec), Message::VideoCodecSelected),
+            ]
+            .spacing(7)
+            .align_y(Center)
+        }));
+        let controls = controls.push_maybe((video && codec.encodes()).then(|| {
+            column![
+                row![
+                    text("Preset / rate control: ").width(200),
+                    pick_list(codec.presets(), self.enc_preset.as_deref(), |p| {
+                        Message::EncoderPresetSelected(p.to_string())
+                    })
+                    .placeholder("Preset"),
+                    pick_list([RateMode::Crf, RateMode::Bitrate], Some(self.enc_rate_mode), Message::RateModeSelected),
+                    text_input(
+                        if self.enc_rate_mode == RateMode::Crf { "CRF" } else { "kbps" },
+                        &self.enc_quality
+                    )
+                    .on_input(Message::QualityChanged)
+                    .width(80),
+                ]
+                .spacing(7)
+                .align_y(Center),
+                row![
+                    text("Pixel format / profile / level: ").width(200),
+                    text_input("e.g. yuv420p", &self.enc_pixel_format)
+                        .on_input(Message::PixelFormatChanged)
+                        .width(120),
+                    pick_list(codec.profiles(), self.enc_profile.as_deref(), |p| {
+                        Message::ProfileSelected(p.to_string())
+                    })
+                    .placeholder("Profile"),
+                    text_input("e.g. 4.1", &self.enc_level)
+                        .on_input(Message::LevelChanged)
+                        .width(80),
+                ]
+                .spacing(7)
+                .align_y(Center),
+            ]
+            .spacing(7)
+        }));
+        Some(controls.push_maybe(audio.then(|| {
+            row![
+                text("Audio codec: ").width(200),
+                pick_list(AudioCodec::ALL, Some(self.enc_audio_codec), Message::AudioCodecSelected),
+            ]
+            .push_maybe(self.enc_audio_codec.has_bitrate().then(|| {
+                text_input("kbps", &self.enc_audio_bitrate)
+                    .on_input(Message::AudioBitrateChanged)
+                    .width(80)
+            }))
+            .spacing(7)
+            .align_y(Center)
+        })))
+    }
+
+    fn chain_controls(&self) -> Column<'_, Message> {
+        let steps = self.chain_steps.iter().enumerate().map(|(i, step)| {
+            let fields = step.kind.fields().iter().enumerate().map(|(f, (label, _))| {
+                let wide = matches!((step.kind, f), (StepKind::Watermark, 0) | (StepKind::Text, 0));
+                text_input(label, &step.fields[f])
+                    .on_input(move |value| Message::ChainFieldChanged(i, f, value))
+                    .width(if wide { Fill } else { 80.into() })
+                    .into()
+            });
+            row![text(format!("{}. {}", i + 1, step.kind)).width(120)]
+                .extend(fields)
+                .push_maybe((step.kind == StepKind::Rotate).then(|| {
+                    pick_list([Turn::Clockwise, Turn::CounterClockwise, Turn::UpsideDown], Some(step.turn), move |turn| {
+                        Message::ChainTurnSelected(i, turn)
+                    })
+                }))
+                .push_maybe(matches!(step.kind, StepKind::Watermark | StepKind::Text).then(|| {
+                    pick_list(Position::ALL, Some(step.position), move |position| Message::ChainPositionSelected(i, position))
+                }))
+                .push(horizontal_space())
+                .push(button(remix_icon("ri-arrow-up-line")).on_press_maybe((i > 0).then_some(Message::ChainStepMovedUp(i))))
+                .push(
+                    button(remix_icon("ri-arrow-down-line"))
+                        .on_press_maybe((i + 1 < self.chain_steps.len()).then_some(Message::ChainStepMovedDown(i))),
+                )
+                .push(button(remix_icon("ri-delete-bin-line")).on_press(Message::ChainStepRemoved(i)))
+                .spacing(7)
+                .align_y(Center)
+                .into()
+        });
+        let problem = match self.chain() {
+            Ok(_) => None,
+            Err(e) => Some(text(e).color(Color::from_rgb(1.0, 0.4, 0.4))),
+        };
+
+        column![]
+            .extend(steps)
+            .push(
+                pick_list(StepKind::ALL, None::<StepKind>, Message::ChainStepAdded).placeholder("Add step"),
+            )
+            .push_maybe(problem.filter(|_| !self.chain_steps.is_empty()))
+            .spacing(7)
+    }
+
+    fn queue_controls(&self) -> Column<'_, Message> {
+        let jobs = Column::with_children(self.jobs.iter().enumerate().map(|(i, job)| {
+            let id = job.id;
+            row![
+                button(text(job.label.clone()))
+                    .style(button::text)
+                    .on_press(Message::JobSelected(id))
+                    .width(Fill),
+                text(job.status.to_string()).width(80),
+                progress_bar(0.0..=100.0, job.progress).width(100).height(10),
+                button(remix_icon("ri-arrow-up-line")).on_press_maybe((i > 0).then_some(Message::JobMovedUp(id))),
+                button(remix_icon("ri-arrow-down-line"))
+                    .on_press_maybe((i + 1 < self.jobs.len()).then_some(Message::JobMovedDown(id))),
+                button(remix_icon("ri-stop-line"))
+                    .on_press_maybe((!job.status.is_finished()).then_some(Message::JobCancelled(id))),
+                button(remix_icon("ri-delete-bin-line"))
+                    .on_press_maybe((job.status != JobStatus::Running).then_some(Message::JobRemoved(id))),
+            ]
+            .spacing(7)
+            .align_y(Center)
+            .into()
+        }))
+        .spacing(3);
+        let log = self
+            .selected_job
+            .and_then(|id| self.jobs.iter().find(|job| job.id == id))
+            .map(|job| self.log_viewer(job));
+
+        column![
+            row![
+                text(format!(
+                    "Queue: {} of {} jobs finished",
+                    self.jobs.iter().filter(|job| job.status.is_finished()).count(),
+                    self.jobs.len()
+                )),
+                horizontal_space(),
+                text("Parallel jobs:"),
+                text_input("1", &self.max_jobs).on_input(Message::MaxJobsChanged).width(50),
+                button(if self.queue_paused { "Resume" } else { "Pause" }).on_press(Message::QueuePauseToggled),
+                button("Clear finished").on_press(Message::ClearFinished),
+            ]
+            .spacing(7)
+            .align_y(Center),
+            container(scrollable(jobs)).max_height(160),
+        ]
+        .push_maybe(log)
+        .spacing(7)
+    }
+
+    fn command_controls(&self) -> Column<'_, Message> {
+        let panel = self.command_preview.then(|| {
+            let editor = text_editor(&self.command).font(Font::MONOSPACE).size(12).height(120);
+            column![
+                row![
+                    button("Refresh").on_press(Message::RefreshCommand),
+                    button("Copy").on_press(Message::CopyCommand),
+                    checkbox("Edit before running", self.command_editable)
+                        .on_toggle_maybe((!self.is_batch()).then_some(Message::CommandEditToggled)),
+                ]
+                .spacing(7)
+                .align_y(Center),
+                if self.command_editable && !self.is_batch() {
+                    editor.on_action(Message::CommandEdited)
+                } else {
+                    editor
+                },
+            ]
+            .push_maybe(self.command_note.clone().map(|note| text(note).size(12)))
+            .spacing(7)
+        });
+        column![checkbox("Command preview", self.command_preview).on_toggle(Message::CommandPreviewToggled)]
+            .push_maybe(panel)
+            .spacing(7)
+    }
+
+    fn overwrite_prompt(&self, targets: &[(String, String)]) -> Column<'_, Message> {
+        let taken: Vec<&str> = targets
+            .iter()
+            .map(|(_, output)| output.as_str())
+            .filter(|output| self.output_taken(output))
+            .collect();
+        let mut names = taken.iter().take(3).copied().collect::<Vec<_>>().join(", ");
+        if taken.len() > 3 {
+            names = format!("{} and {} more", names, taken.len() - 3);
+        }
+        column![
+            text(if taken.len() == 1 {
+                format!("{} already exists.", names)
+            } else {
+                format!("{} outputs already exist: {}", taken.len(), names)
+            }),
+            row![
+                button("Overwrite").on_press(Message::OverwriteChosen),
+                button("Auto-rename").on_press(Message::AutoRenameChosen),
+                button("Cancel").on_press(Message::OverwriteCancelled),
+            ]
+            .spacing(7),
+        ]
+        .spacing(7)
+    }
+
+    fn log_viewer<'a>(&self, job: &'a Job) -> Column<'a, Message> {
+        let search = self.log_search.trim().to_lowercase();
+        // one text block with a colored span per line, thousands of widgets would crawl
+        let lines: Vec<_> = job
+            .log
+            .iter()
+            .filter(|line| line.level <= self.log_level)
+            .filter(|line| search.is_empty() || line.text.to_lowercase().contains(&search))
+            .map(|line| {
+                let color = match line.level {
+                    LogLevel::Error => Some(Color::from_rgb(1.0, 0.4, 0.4)),
+                    LogLevel::Warning => Some(Color::from_rgb(1.0, 0.8, 0.3)),
+                    LogLevel::Info => None,
+                };
+                span(format!("{}\n", line.text)).color_maybe(color)
+            })
+            .collect();
+        // anchored at the end the view follows new lines while it is scrolled to the bottom
+        let anchor = if self.log_autoscroll { Anchor::End } else { Anchor::Start };
+
+        column![
+            row![
+                text(format!("Log: {}", job.label)).width(Fill),
+                pick_list(LogLevel::ALL, Some(self.log_level), Message::LogLevelSelected),
+                text_input("Search", &self.log_search).on_input(Message::LogSearchChanged).width(160),
+                checkbox("Auto-scroll", self.log_autoscroll).on_toggle(Message::LogAutoScrollToggled),
+                button("Save log").on_press(Message::SaveLog),
+            ]
+            .spacing(7)
+            .align_y(Center),
+            container(scrollable(rich_text(lines).font(Font::MONOSPACE).size(12).width(Fill)).anchor_y(anchor)).max_height(200),
+        ]
+        .spacing(7)
+    }
+
+    fn binary_controls(&self) -> Column<'_, Message> {
+        column![
+            row![
+                text("ffmpeg: ").width(200),
+                text_input("ffmpeg", &self.ffmpeg_path).on_input(Message::FfmpegPathChanged),
+                button("Browse").on_press(Message::BrowseFfmpeg),
+            ]
+            .spacing(7)
+            .align_y(Center),
+            row![
+                text("ffprobe: ").width(200),
+                text_input("ffprobe", &self.ffprobe_path).on_input(Message::FfprobePathChanged),
+                button("Browse").on_press(Message::BrowseFfprobe),
+            ]
+            .spacing(7)
+            .align_y(Center),
+            row![
+                button("Save and check").on_press(Message::SaveSettings),
+                text(match &self.capabilities {
+                    Some(capabilities) => format!("Found ffmpeg {}", capabilities.version),
+                    None => "ffmpeg not found".to_string(),
+                }),
+            ]
+            .spacing(7)
+            .align_y(Center),
+        ]
+        .spacing(7)
+    }
+
+    // Shown instead of the operations until a usable ffmpeg is configured
+    fn setup_view(&self, problem: &str, can_ignore: bool) -> Container<'_, Message> {
+        container(
+            column![
+                text("FFmpeg setup").size(24),
+                text(problem.to_string()),
+                self.binary_controls(),
+            ]
+            .push_maybe(can_ignore.then(|| button("Continue anyway").on_press(Message::IgnoreVersion)))
+            .spacing(15)
+            .max_width(700),
+        )
+        .padding(30)
+        .width(Fill)
+        .height(Fill)
+        .center_x(Fill)
+        .center_y(Fill)
+    }
 
-                Task::perform(save_file(&["mp4", "mkv"]), Message::OutputVideoOpened)
+    fn view(&self) -> Container<'_, Message> {
+        match &self.ffmpeg_status {
+            FfmpegStatus::Ready => {}
+            FfmpegStatus::Checking => {
+                return container(text("Looking for ffmpeg...")).width(Fill).height(Fill).center_x(Fill).center_y(Fill)
             }
-            Message::OutputVideoOpened(result) => {
-                match result {
-                    Ok(file_path) => {
-                        // Check if the file_path ends with ".mp4"
-                        if !file_path.ends_with(".mp4") {
-                            // Append ".mp4" to the file_path
-                            self.dest = format!("{}.mp4", file_path);
-                        } else {
-                            self.dest = file_path;
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Error selecting file: {}", e); // Handle the error (optional)
-                                                                  // Do nothing if the file selection failed
-                    }
-                }
-
-                Task::none()
-            },
+            FfmpegStatus::Missing(problem) => return self.setup_view(problem, false),
+            FfmpegStatus::Outdated(problem) => return self.setup_view(problem, true),
         }
-    }
 
-    fn view(&self) -> Container<Message> {
         // &self.update(Message::Start);
 
+        let (output_label, output_button, output_message) = if self.action == "frames" {
+            ("Output folder: ", "Choose folder", Message::SelectOutputFolder)
+        } else {
+            ("Output video: ", "Choose destination", Message::SelectOutputVideo)
+        };
+
+        let missing = self.missing_capabilities();
         let controls = column![]
             .spacing(7)
-            .push(
+            .push_maybe((self.action != "settings").then(|| {
+                row![
+                    text("Preset: ").width(200),
+                    pick_list(self.saved_presets.clone(), self.selected_preset(), Message::PresetSelected)
+                    .placeholder("Load preset"),
+                    text_input("Preset name", &self.preset_name)
+                        .on_input(Message::PresetNameChanged)
+                        .width(160),
+                    button("Save").on_press_maybe(non_empty(&self.preset_name).map(|_| Message::SavePreset)),
+                    button("Delete").on_press_maybe(self.selected_preset().map(|_| Message::DeletePreset)),
+                    button("Import").on_press(Message::ImportPreset),
+                    button("Export").on_press(Message::ExportPreset),
+                ]
+                .spacing(7)
+                .align_y(Center)
+            }))
+            .push_maybe((self.action != "settings").then(|| if self.action == "slideshow" {
+                row![
+                    text("Input images: ").width(200),
+                    button("Choose images").on_press(Message::SelectImages),
+                    button("Choose folder").on_press(Message::SelectImageFolder),
+                    container(text(format!("{} images", self.slide_images.len())))
+                        .align_x(End)
+                        .width(Fill)
+                        .padding(7),
+                ]
+                .spacing(7)
+                .align_y(Center)
+            } else if self.is_batch() {
+                row![
+                    text("Input videos: ").width(200),
+                    button("Choose files").on_press(Message::SelectInputFiles),
+                    button("Choose folder").on_press(Message::SelectInputFolder),
+                    button("Single file").on_press(Message::ClearBatch),
+                    container(text(format!("{} files", self.batch_inputs.len())))
+                        .align_x(End)
+                        .width(Fill)
+                        .padding(7),
+                ]
+                .spacing(7)
+                .align_y(Center)
+            } else {
                 row![
                     text("Input video: ").width(200),
                     button("Choose source").on_press(Message::SelectInputVideo),
+                ]
+                .push_maybe((self.action != "streams").then(|| button("Batch files").on_press(Message::SelectInputFiles)))
+                .push_maybe((self.action != "streams").then(|| button("Batch folder").on_press(Message::SelectInputFolder)))
+                .push(
                     container(text(self.source.clone()))
                         .align_x(End)
                         .width(Fill)
                         .padding(7),
+                )
+                .spacing(7)
+                .align_y(Center)
+            }))
+            .push_maybe((self.is_batch() && self.action != "analyze").then(|| {
+                row![
+                    text("Output folder: ").width(200),
+                    button("Choose folder").on_press(Message::SelectBatchFolder),
+                    text_input("{name}_{action}", &self.batch_template)
+                        .on_input(Message::BatchTemplateChanged)
+                        .width(200),
+                    container(text(self.batch_dir.clone()))
+                        .align_x(End)
+                        .width(Fill)
+                        .padding(7),
                 ]
-                .align_y(Center),
-            )
-            .push(
+                .spacing(7)
+                .align_y(Center)
+            }))
+            .push_maybe((!self.is_batch() && !matches!(self.action.as_str(), "analyze" | "settings")).then(|| {
                 row![
-                    text("Output video: ").width(200),
-                    button("Choose destination").on_press(Message::SelectOutputVideo),
+                    text(output_label).width(200),
+                    button(output_button).on_press(output_message),
                     container(text(self.dest.clone()))
                         .align_x(End)
                         .width(Fill)
                         .padding(7),
                 ]
-                .align_y(Center),
-            )
+                .align_y(Center)
+            }))
             .push_maybe(self.can_image().then(|| {
                 row![
                     text("Image input: ").width(200),
@@ -354,12 +2372,465 @@ impl Controller {
                 ]
                 .align_y(Center)
             }))
+            .push_maybe((self.action == "speed").then(|| {
+                row![
+                    text("Speed: ").width(200),
+                    text_input("e.g. 2 or 0.5", &self.speed_factor)
+                        .on_input(Message::SpeedChanged)
+                        .width(120),
+                    pick_list(
+                        SPEED_PRESETS,
+                        SPEED_PRESETS
+                            .iter()
+                            .copied()
+                            .find(|p| self.speed_factor == p.0.to_string()),
+                        Message::SpeedPresetSelected,
+                    )
+                    .placeholder("Presets"),
+                    checkbox("Drop audio (timelapse)", self.speed_drop_audio)
+                        .on_toggle(Message::SpeedDropAudioToggled),
+                ]
+                .spacing(7)
+                .align_y(Center)
+            }))
+            .push_maybe((self.action == "animation").then(|| {
+                column![
+                    row![
+                        text("Format: ").width(200),
+                        pick_list(
+                            [AnimationFormat::Gif, AnimationFormat::WebP],
+                            Some(self.anim_format),
+                            Message::AnimationFormatSelected,
+                        ),
+                        text("Dither: "),
+                        pick_list(
+                            [
+                                Dither::Sierra2_4a,
+                                Dither::Sierra2,
+                                Dither::FloydSteinberg,
+                                Dither::Bayer,
+                                Dither::None,
+                            ],
+                            Some(self.anim_dither),
+                            Message::AnimationDitherSelected,
+                        ),
+                    ]
+                    .spacing(7)
+                    .align_y(Center),
+                    row![
+                        text("Fps / width / loops: ").width(200),
+                        text_input("fps", &self.anim_fps)
+                            .on_input(Message::AnimationFpsChanged)
+                            .width(80),
+                        text_input("width", &self.anim_width)
+                            .on_input(Message::AnimationWidthChanged)
+                            .width(80),
+                        text_input("0 = forever", &self.anim_loop)
+                            .on_input(Message::AnimationLoopChanged)
+                            .width(120),
+                    ]
+                    .spacing(7)
+                    .align_y(Center),
+                    row![
+                        text("Range: ").width(200),
+                        text_input("start (e.g. 00:00:05)", &self.anim_start)
+                            .on_input(Message::AnimationStartChanged)
+                            .width(180),
+                        text_input("end (e.g. 00:00:12.5)", &self.anim_end)
+                            .on_input(Message::AnimationEndChanged)
+                            .width(180),
+                    ]
+                    .spacing(7)
+                    .align_y(Center),
+                ]
+                .spacing(7)
+            }))
+            .push_maybe((self.action == "frames").then(|| {
+                column![
+                    row![
+                        text("Frames: ").width(200),
+                        pick_list(
+                            [FrameMode::EveryFrame, FrameMode::Interval, FrameMode::Keyframes, FrameMode::Single],
+                            Some(self.frames_mode),
+                            Message::FrameModeSelected,
+                        ),
+                    ]
+                    .push_maybe((self.frames_mode == FrameMode::Interval).then(|| {
+                        text_input("seconds", &self.frames_interval)
+                            .on_input(Message::FrameIntervalChanged)
+                            .width(100)
+                    }))
+                    .push_maybe((self.frames_mode == FrameMode::Single).then(|| {
+                        text_input("timestamp", &self.frames_timestamp)
+                            .on_input(Message::FrameTimestampChanged)
+                            .width(140)
+                    }))
+                    .spacing(7)
+                    .align_y(Center),
+                    row![
+                        text("File name: ").width(200),
+                        text_input("frame_%04d", &self.frames_pattern)
+                            .on_input(Message::FramePatternChanged)
+                            .width(200),
+                        pick_list(
+                            [ImageFormat::Png, ImageFormat::Jpg, ImageFormat::WebP],
+                            Some(self.frames_format),
+                            Message::FrameFormatSelected,
+                        ),
+                    ]
+                    .spacing(7)
+                    .align_y(Center),
+                ]
+                .spacing(7)
+            }))
+            .push_maybe((self.action == "slideshow").then(|| {
+                column![
+                    row![
+                        text("Mode: ").width(200),
+                        pick_list(
+                            [SlideshowMode::Sequence, SlideshowMode::Slideshow],
+                            Some(self.slide_mode),
+                            Message::SlideshowModeSelected,
+                        ),
+                    ]
+                    .spacing(7)
+                    .align_y(Center),
+                    row![
+                        text("Size / fps: ").width(200),
+                        text_input("1920x1080", &self.slide_size)
+                            .on_input(Message::SlideshowSizeChanged)
+                            .width(140),
+                        text_input("fps", &self.slide_fps)
+                            .on_input(Message::SlideshowFpsChanged)
+                            .width(80),
+                    ]
+                    .spacing(7)
+                    .align_y(Center),
+                ]
+                .push_maybe((self.slide_mode == SlideshowMode::Slideshow).then(|| {
+                    row![
+                        text("Duration / crossfade: ").width(200),
+                        text_input("seconds per image", &self.slide_duration)
+                            .on_input(Message::SlideshowDurationChanged)
+                            .width(140),
+                        text_input("crossfade seconds", &self.slide_crossfade)
+                            .on_input(Message::SlideshowCrossfadeChanged)
+                            .width(140),
+                        checkbox("Ken Burns zoom", self.slide_ken_burns)
+                            .on_toggle(Message::SlideshowKenBurnsToggled),
+                    ]
+                    .spacing(7)
+                    .align_y(Center)
+                }))
+                .push(
+                    row![
+                        text("Background audio: ").width(200),
+                        button("Choose audio").on_press(Message::SelectSlideshowAudio),
+                        container(text(self.slide_audio.clone()))
+                            .align_x(End)
+                            .width(Fill)
+                            .padding(7),
+                    ]
+                    .align_y(Center),
+                )
+                .spacing(7)
+            }))
+            .push_maybe((self.action == "extract_audio").then(|| {
+                let streams: Vec<Stream> = self
+                    .probe
+                    .as_ref()
+                    .map(|p| p.audio_streams().into_iter().cloned().collect())
+                    .unwrap_or_default();
+                column![
+                    row![
+                        text("Audio stream: ").width(200),
+                        pick_list(streams, self.extract_stream.clone(), |s| Message::ExtractStreamSelected(Box::new(s)))
+                            .placeholder("Choose an input to list its audio streams"),
+                    ]
+                    .spacing(7)
+                    .align_y(Center),
+                    row![
+                        text("Output: ").width(200),
+                        pick_list(
+                            [AudioTarget::Copy, AudioTarget::Mp3, AudioTarget::Flac, AudioTarget::Wav, AudioTarget::Opus],
+                            Some(self.extract_target),
+                            Message::ExtractTargetSelected,
+                        ),
+                    ]
+                    .push_maybe(self.extract_target.has_bitrate().then(|| {
+                        text_input("kbps", &self.extract_bitrate)
+                            .on_input(Message::ExtractBitrateChanged)
+                            .width(80)
+                    }))
+                    .spacing(7)
+                    .align_y(Center),
+                ]
+                .spacing(7)
+            }))
+            .push_maybe((self.action == "normalize").then(|| {
+                column![
+                    row![
+                        text("Target: ").width(200),
+                        pick_list(
+                            [
+                                LoudnessPreset::Streaming,
+                                LoudnessPreset::Podcast,
+                                LoudnessPreset::Broadcast,
+                                LoudnessPreset::Custom,
+                            ],
+                            Some(self.norm_preset),
+                            Message::NormalizePresetSelected,
+                        ),
+                    ]
+                    .spacing(7)
+                    .align_y(Center),
+                    row![
+                        text("LUFS / true peak / LRA: ").width(200),
+                        text_input("LUFS", &self.norm_integrated)
+                            .on_input(Message::NormalizeIntegratedChanged)
+                            .width(80),
+                        text_input("dBTP", &self.norm_true_peak)
+                            .on_input(Message::NormalizeTruePeakChanged)
+                            .width(80),
+                        text_input("LU", &self.norm_range)
+                            .on_input(Message::NormalizeRangeChanged)
+                            .width(80),
+                    ]
+                    .spacing(7)
+                    .align_y(Center),
+                ]
+                .spacing(7)
+            }))
+            .push_maybe((self.action == "analyze").then(|| {
+                let level = |value: Option<f64>, unit: &str| match value {
+                    Some(value) => format!("{:.1} {}", value, unit),
+                    None => "-".to_string(),
+                };
+                let report = self.loudness_report.clone().unwrap_or_default();
+                column![
+                    row![
+                        text("Integrated / LRA / true peak: ").width(200),
+                        text(format!(
+                            "{}  /  {}  /  {}",
+                            level(report.integrated, "LUFS"),
+                            level(report.range, "LU"),
+                            level(report.true_peak, "dBFS")
+                        )),
+                    ]
+                    .spacing(7),
+                    row![
+                        text("Mean / max volume: ").width(200),
+                        text(format!("{}  /  {}", level(report.mean_volume, "dB"), level(report.max_volume, "dB"))),
+                    ]
+                    .spacing(7),
+                ]
+                .extend(report.channels.iter().map(|channel| {
+                    row![
+                        text(format!("Channel {}: ", channel.channel)).width(200),
+                        text(format!("RMS {}  /  peak {}", level(channel.rms, "dB"), level(channel.peak, "dB"))),
+                    ]
+                    .spacing(7)
+                    .into()
+                }))
+                .push(
+                    button("Export JSON")
+                        .on_press_maybe(self.loudness_report.is_some().then_some(Message::ExportReport)),
+                )
+                .spacing(7)
+            }))
+            .push_maybe((self.action == "adjust_audio").then(|| {
+                column![
+                    row![
+                        text("Gain (dB): ").width(200),
+                        text_input("0", &self.adjust_gain)
+                            .on_input(Message::AdjustGainChanged)
+                            .width(80),
+                    ]
+                    .spacing(7)
+                    .align_y(Center),
+                    row![
+                        text("Fade in / out (s): ").width(200),
+                        text_input("0", &self.adjust_fade_in)
+                            .on_input(Message::AdjustFadeInChanged)
+                            .width(80),
+                        text_input("0", &self.adjust_fade_out)
+                            .on_input(Message::AdjustFadeOutChanged)
+                            .width(80),
+                    ]
+                    .spacing(7)
+                    .align_y(Center),
+                    row![
+                        text("Audio offset (s): ").width(200),
+                        text_input("0", &self.adjust_offset)
+                            .on_input(Message::AdjustOffsetChanged)
+                            .width(80),
+                        text("positive delays the audio, negative plays it earlier"),
+                    ]
+                    .spacing(7)
+                    .align_y(Center),
+                ]
+                .spacing(7)
+            }))
+            .push_maybe((self.action == "remove_silence").then(|| {
+                let removed: f64 = self.silences.iter().map(|s| s.end - s.start).sum();
+                column![
+                    row![
+                        text("Threshold (dB) / min (s): ").width(200),
+                        text_input("-35", &self.silence_threshold)
+                            .on_input(Message::SilenceThresholdChanged)
+                            .width(80),
+                        text_input("0.5", &self.silence_min)
+                            .on_input(Message::SilenceMinChanged)
+                            .width(80),
+                        button(if self.detecting_silence { "Detecting..." } else { "Detect silence" })
+                            .on_press_maybe((!self.detecting_silence).then_some(Message::DetectSilence)),
+                    ]
+                    .spacing(7)
+                    .align_y(Center),
+                    row![
+                        text("Padding (s): ").width(200),
+                        text_input("0.1", &self.silence_padding)
+                            .on_input(Message::SilencePaddingChanged)
+                            .width(80),
+                    ]
+                    .spacing(7)
+                    .align_y(Center),
+                    text(format!("{} silent ranges, {} in total", self.silences.len(), format_time(removed))),
+                ]
+                .extend(self.silences.iter().take(8).map(|s| {
+                    text(format!("{} - {}", format_time(s.start), format_time(s.end))).into()
+                }))
+                .push_maybe((self.silences.len() > 8).then(|| text(format!("... and {} more", self.silences.len() - 8))))
+                .spacing(7)
+            }))
+            .push_maybe((self.action == "channels").then(|| {
+                let layout = match self.source_audio() {
+                    Some(stream) => format!(
+                        "{} ({} channels)",
+                        stream.channel_layout.clone().unwrap_or("unknown".to_string()),
+                        stream.channels.unwrap_or_default()
+                    ),
+                    None => "Choose an input to read its channel layout".to_string(),
+                };
+                column![
+                    row![text("Source layout: ").width(200), text(layout)].spacing(7),
+                    row![
+                        text("Mapping: ").width(200),
+                        pick_list(
+                            [
+                                ChannelMapping::MonoToStereo,
+                                ChannelMapping::LeftToBoth,
+                                ChannelMapping::RightToBoth,
+                                ChannelMapping::SwapChannels,
+                                ChannelMapping::DownmixStandard,
+                                ChannelMapping::DownmixWithLfe,
+                                ChannelMapping::DownmixDialogue,
+                            ],
+                            Some(self.channel_mapping),
+                            Message::ChannelMappingSelected,
+                        ),
+                    ]
+                    .spacing(7)
+                    .align_y(Center),
+                ]
+                .spacing(7)
+            }))
+            .push_maybe((self.action == "streams").then(|| {
+                column![]
+                    .extend(self.tracks.iter().enumerate().map(|(i, track)| {
+                        let source = if track.input == 0 {
+                            "source".to_string()
+                        } else {
+                            Path::new(&self.track_inputs[track.input])
+                                .file_name()
+                                .map(|n| n.to_string_lossy().to_string())
+                                .unwrap_or_default()
+                        };
+                        row![
+                            checkbox(format!("#{} {} {} ({})", track.stream, track.kind, track.codec, source), track.enabled)
+                                .on_toggle(move |enabled| Message::TrackToggled(i, enabled))
+                                .width(Fill),
+                            text_input("lang", &track.language)
+                                .on_input(move |value| Message::TrackLanguageChanged(i, value))
+                                .width(60),
+                            text_input("title", &track.title)
+                                .on_input(move |value| Message::TrackTitleChanged(i, value))
+                                .width(160),
+                            checkbox("default", track.default)
+                                .on_toggle(move |default| Message::TrackDefaultToggled(i, default)),
+                            button(remix_icon("ri-arrow-up-line")).on_press_maybe((i > 0).then_some(Message::TrackMovedUp(i))),
+                            button(remix_icon("ri-arrow-down-line"))
+                                .on_press_maybe((i + 1 < self.tracks.len()).then_some(Message::TrackMovedDown(i))),
+                        ]
+                        .spacing(7)
+                        .align_y(Center)
+                        .into()
+                    }))
+                    .push_maybe(self.tracks.is_empty().then(|| text("Choose an input to list its audio and subtitle streams")))
+                    .push_maybe(self.dropped_streams().map(text))
+                    .push(button("Add external track").on_press(Message::AddTrack))
+                    .spacing(7)
+            }))
+            .push_maybe((self.action == "convert").then(|| {
+                column![
+                    row![
+                        text("Container: ").width(200),
+                        pick_list(
+                            [ContainerFormat::Mp4, ContainerFormat::Mkv, ContainerFormat::Mov, ContainerFormat::WebM, ContainerFormat::Ts],
+                            Some(self.container_format),
+                            Message::ContainerSelected,
+                        ),
+                    ]
+                    .spacing(7)
+                    .align_y(Center),
+                ]
+                .extend(self.probe.iter().flat_map(|p| p.streams.iter()).map(|stream| {
+                    row![
+                        text(format!("{}: ", stream)).width(Fill),
+                        text(self.container_format.action_for(stream).to_string()),
+                    ]
+                    .spacing(7)
+                    .into()
+                }))
+                .spacing(7)
+            }))
+            .push_maybe((self.action == "chain").then(|| self.chain_controls()))
+            .push_maybe((self.action == "custom").then(|| {
+                column![
+                    row![
+                        text("Input options: ").width(200),
+                        text_input("-ss 10 -t 30", &self.custom_input_options).on_input(Message::CustomInputOptionsChanged),
+                    ]
+                    .spacing(7)
+                    .align_y(Center),
+                    row![
+                        text("-filter_complex: ").width(200),
+                        text_input("[0:v]hflip[v]", &self.custom_filter).on_input(Message::CustomFilterChanged),
+                    ]
+                    .spacing(7)
+                    .align_y(Center),
+                    row![
+                        text("Output options: ").width(200),
+                        text_input("-map [v] -map 0:a? -movflags +faststart", &self.custom_output_options)
+                            .on_input(Message::CustomOutputOptionsChanged),
+                    ]
+                    .spacing(7)
+                    .align_y(Center),
+                ]
+                .spacing(7)
+            }))
+            .push_maybe((self.action == "settings").then(|| self.binary_controls()))
+            .push_maybe(self.encoding_controls())
+            .push_maybe((!missing.is_empty()).then(|| {
+                text(format!("Unavailable in your ffmpeg build: {}", missing.join(", "))).color(Color::from_rgb(1.0, 0.4, 0.4))
+            }))
+            .push_maybe((self.action != "settings").then(|| self.command_controls()))
             ;
 
 
         let toasts  = Column::with_children(
-            self.toasts.iter()
-                .map(|message| {
+            self.toasts.iter().enumerate()
+                .map(|(i, message)| {
                     let mut _bg_color = Color::from_rgb(58.0 / 255.0, 132.0 / 255.0, 0.0); // Default color #3a8400
 
                     // Check if the "type" key exists and is a string
@@ -375,8 +2846,21 @@ impl Controller {
                         .background(_bg_color).color(Color::from_rgb(1.0, 1.0, 1.0))
                         .border(Border::default().rounded(4.0));
 
-                    let message_text = container(text(message.get("message").unwrap()))
-                        .style(move|_theme: &Theme| msg_style.clone())
+                    // the ffmpeg log of a failure stays folded until asked for
+                    let expanded = message.contains_key("expanded");
+                    let details = message.get("details").map(|details| {
+                        let toggle = button(text(if expanded { "Hide details" } else { "Details" }).size(12))
+                            .style(button::text)
+                            .on_press(Message::ToastDetailsToggled(i));
+                        let log = expanded.then(|| {
+                            container(scrollable(text(details.clone()).font(Font::MONOSPACE).size(12)).width(Fill))
+                                .max_height(200)
+                        });
+                        column![toggle].push_maybe(log)
+                    });
+
+                    let message_text = container(column![text(message.get("message").unwrap())].push_maybe(details))
+                        .style(move|_theme: &Theme| msg_style)
                         .padding(7).width(Fill); // Use a closure that returns the style
 
                     Column::new().push(message_text).push(horizontal_space().height(7))
@@ -385,187 +2869,77 @@ impl Controller {
 
         container(column![
             container(column![
-                container(
-                    row![
-                        button(
-                            container(column![
-                                remix_icon("ri-volume-mute-line")
-                                    .size(35)
-                                    .width(Fill)
-                                    .align_x(Center),
-                                text("Mute video").width(Fill).align_x(Center),
-                            ])
-                            .width(Fill)
-                            .align_x(Center)
-                        )
-                        .width(Fill)
-                        .on_press(Message::Mute),
-                        button(
-                            container(column![
-                                remix_icon("ri-clockwise-2-line")
-                                    .size(35)
-                                    .width(Fill)
-                                    .align_x(Center),
-                                text("Rotate video").width(Fill).align_x(Center),
-                            ])
-                            .width(Fill)
-                            .align_x(Center)
-                        )
-                        .width(Fill)
-                        .on_press(Message::Rotate),
-                        button(
-                            container(column![
-                                remix_icon("ri-voiceprint-fill")
-                                    .size(35)
-                                    .width(Fill)
-                                    .align_x(Center),
-                                text("Replace sound").width(Fill).align_x(Center),
-                            ])
-                            .width(Fill)
-                            .align_x(Center)
-                        )
-                        .width(Fill)
-                        .on_press(Message::ReplaceSound),
-                        button(
-                            container(column![
-                                remix_icon("ri-crop-line")
-                                    .size(35)
-                                    .width(Fill)
-                                    .align_x(Center),
-                                text("Crop video").width(Fill).align_x(Center),
-                            ])
-                            .width(Fill)
-                            .align_x(Center)
-                        )
-                        .width(Fill)
-                        .on_press(Message::Crop),
-                    ]
-                    .spacing(15)
-                ),
+                row![
+                    action_button("ri-volume-mute-line", "Mute video", Message::Mute),
+                    action_button("ri-clockwise-2-line", "Rotate video", Message::Rotate),
+                    action_button("ri-voiceprint-fill", "Replace sound", Message::ReplaceSound),
+                    action_button("ri-crop-line", "Crop video", Message::Crop),
+                ]
+                .spacing(15),
                 container("").height(15),
-                container(
-                    row![
-                        button(
-                            container(column![
-                                remix_icon("ri-file-zip-line")
-                                    .size(35)
-                                    .width(Fill)
-                                    .align_x(Center),
-                                text("Shrink video").width(Fill).align_x(Center),
-                            ])
-                            .width(Fill)
-                            .align_x(Center)
-                        )
-                        .width(Fill)
-                        .on_press(Message::Compress),
-                        button(
-                            container(column![
-                                remix_icon("ri-expand-diagonal-line")
-                                    .size(35)
-                                    .width(Fill)
-                                    .align_x(Center),
-                                text("Resize video").width(Fill).align_x(Center),
-                            ])
-                            .width(Fill)
-                            .align_x(Center)
-                        )
-                        .width(Fill)
-                        .on_press(Message::Resize),
-                        button(
-                            container(column![
-                                remix_icon("ri-text-snippet")
-                                    .size(35)
-                                    .width(Fill)
-                                    .align_x(Center),
-                                text("Replace sound").width(Fill).align_x(Center),
-                            ])
-                            .width(Fill)
-                            .align_x(Center)
-                        )
-                        .width(Fill)
-                        .on_press(Message::Subtitle),
-                        button(
-                            container(column![
-                                remix_icon("ri-image-add-line")
-                                    .size(35)
-                                    .width(Fill)
-                                    .align_x(Center),
-                                text("Watermark").width(Fill).align_x(Center),
-                            ])
-                            .width(Fill)
-                            .align_x(Center)
-                        )
-                        .width(Fill)
-                        .on_press(Message::Watermark),
-                    ]
-                    .spacing(15)
-                ),
+                row![
+                    action_button("ri-file-zip-line", "Shrink video", Message::Compress),
+                    action_button("ri-expand-diagonal-line", "Resize video", Message::Resize),
+                    action_button("ri-text-snippet", "Replace sound", Message::Subtitle),
+                    action_button("ri-image-add-line", "Watermark", Message::Watermark),
+                ]
+                .spacing(15),
                 container("").height(15),
-                container(
-                    row![
-                        button(
-                            container(column![
-                                remix_icon("ri-split-cells-horizontal")
-                                    .size(35)
-                                    .width(Fill)
-                                    .align_x(Center),
-                                text("Landscape to portrait").width(Fill).align_x(Center),
-                            ])
-                            .width(Fill)
-                            .align_x(Center)
-                        )
-                        .width(Fill)
-                        .on_press(Message::Landscape),
-                        button(
-                            container(column![
-                                remix_icon("ri-git-merge-line")
-                                    .size(35)
-                                    .width(Fill)
-                                    .align_x(Center),
-                                text("Merge two video").width(Fill).align_x(Center),
-                            ])
-                            .width(Fill)
-                            .align_x(Center)
-                        )
-                        .width(Fill)
-                        .on_press(Message::Merge),
-                        button(
-                            container(column![
-                                remix_icon("ri-image-circle-fill")
-                                    .size(35)
-                                    .width(Fill)
-                                    .align_x(Center),
-                                text("Add cover").width(Fill).align_x(Center),
-                            ])
-                            .width(Fill)
-                            .align_x(Center)
-                        )
-                        .width(Fill)
-                        .on_press(Message::AddCover),
-                        button(
-                            container(column![
-                                remix_icon("ri-text-snippet")
-                                    .size(35)
-                                    .width(Fill)
-                                    .align_x(Center),
-                                text("Text watermark").width(Fill).align_x(Center),
-                            ])
-                            .width(Fill)
-                            .align_x(Center)
-                        )
-                        .width(Fill)
-                        .on_press(Message::TextWatermark),
-                    ]
-                    .spacing(15)
-                ),
+                row![
+                    action_button("ri-split-cells-horizontal", "Landscape to portrait", Message::Landscape),
+                    action_button("ri-git-merge-line", "Merge two video", Message::Merge),
+                    action_button("ri-image-circle-fill", "Add cover", Message::AddCover),
+                    action_button("ri-text-snippet", "Text watermark", Message::TextWatermark),
+                ]
+                .spacing(15),
+                container("").height(15),
+                row![
+                    action_button("ri-speed-up-line", "Change speed", Message::Speed),
+                    action_button("ri-file-gif-line", "GIF / WebP", Message::Animation),
+                    action_button("ri-film-line", "Extract frames", Message::Frames),
+                    action_button("ri-slideshow-line", "Images to video", Message::Slideshow),
+                ]
+                .spacing(15),
+                container("").height(15),
+                row![
+                    action_button("ri-music-2-line", "Extract audio", Message::ExtractAudio),
+                    action_button("ri-equalizer-line", "Normalize audio", Message::Normalize),
+                    action_button("ri-bar-chart-line", "Loudness report", Message::Analyze),
+                    action_button("ri-volume-up-line", "Adjust audio", Message::AdjustAudio),
+                ]
+                .spacing(15),
+                container("").height(15),
+                row![
+                    action_button("ri-scissors-cut-line", "Remove silence", Message::RemoveSilence),
+                    action_button("ri-surround-sound-line", "Audio channels", Message::Channels),
+                    action_button("ri-stack-line", "Stream manager", Message::Streams),
+                    action_button("ri-exchange-line", "Convert", Message::Convert),
+                ]
+                .spacing(15),
+                container("").height(15),
+                row![
+                    action_button("ri-links-line", "Operation chain", Message::Chain),
+                    action_button("ri-terminal-box-line", "Custom", Message::Custom),
+                    horizontal_space(),
+                    horizontal_space(),
+                ]
+                .spacing(15),
             ])
             .padding(15),
             container(
                 column![
                     horizontal_space(),
-                    container(row![text("Current action:"), text(self.action.clone())].spacing(15)),
+                    container(
+                        row![
+                            text("Current action:"),
+                            text(self.action.clone()),
+                            button(remix_icon("ri-settings-3-line")).on_press(Message::Settings),
+                        ]
+                        .spacing(15)
+                        .align_y(Center)
+                    ),
                     horizontal_space(),
-                    container(controls),
+                    scrollable(container(controls).padding([0, 15])).height(Fill),
                     horizontal_space(),
                 ]
                 .align_x(Center)
@@ -575,9 +2949,11 @@ impl Controller {
             .height(Fill),
             container(
                 column![
-                    progress_bar(0.0..=100.0, self.progress.clone()),
+                    column![].push_maybe((!self.jobs.is_empty()).then(|| self.queue_controls())),
+                    column![].push_maybe(self.pending_outputs.as_ref().map(|(targets, _)| self.overwrite_prompt(targets))),
+                    progress_bar(0.0..=100.0, self.progress),
                     button(container(text("Do it!")).width(Fill).align_x(Center))
-                        .on_press(Message::DoIt),
+                        .on_press_maybe((missing.is_empty() && self.action != "settings").then_some(Message::DoIt)),
                     toasts,
                 ]
                 .spacing(15)
@@ -598,6 +2974,59 @@ pub enum Error {
     IoError(io::ErrorKind),
 }
 
+fn action_button<'a>(icon: &'a str, label: &'a str, message: Message) -> Button<'a, Message> {
+    button(
+        container(column![
+            remix_icon(icon)
+                .size(35)
+                .width(Fill)
+                .align_x(Center),
+            text(label).width(Fill).align_x(Center),
+        ])
+        .width(Fill)
+        .align_x(Center)
+    )
+    .width(Fill)
+    .on_press(message)
+}
+
+// `path` with the `from` executable name swapped for `to`, if that file exists
+fn sibling_binary(path: &str, from: &str, to: &str) -> Option<String> {
+    let path = Path::new(path);
+    let name = path.file_name()?.to_string_lossy().replacen(from, to, 1);
+    let sibling = path.with_file_name(name);
+    sibling.is_file().then(|| sibling.to_string_lossy().to_string())
+}
+
+// Message that switches to `action`, the way its button does
+fn action_message(action: &str) -> Option<Message> {
+    let message = match action {
+        "mute" => Message::Mute,
+        "rotate" => Message::Rotate,
+        "speed" => Message::Speed,
+        "animation" => Message::Animation,
+        "frames" => Message::Frames,
+        "slideshow" => Message::Slideshow,
+        "extract_audio" => Message::ExtractAudio,
+        "normalize" => Message::Normalize,
+        "analyze" => Message::Analyze,
+        "adjust_audio" => Message::AdjustAudio,
+        "remove_silence" => Message::RemoveSilence,
+        "channels" => Message::Channels,
+        "streams" => Message::Streams,
+        "convert" => Message::Convert,
+        "chain" => Message::Chain,
+        "custom" => Message::Custom,
+        _ => return None,
+    };
+    Some(message)
+}
+
+fn format_time(seconds: f64) -> String {
+    let minutes = (seconds / 60.0).floor();
+    format!("{:02}:{:04.1}", minutes, seconds - minutes * 60.0)
+}
+
 async fn open_file(support_ext: &[impl ToString]) -> Result<String, String> {
     println!("Opening file...");
     let picked_file = rfd::AsyncFileDialog::new()
@@ -621,6 +3050,53 @@ async fn open_file(support_ext: &[impl ToString]) -> Result<String, String> {
 
     Ok(path.to_string())
 }
+async fn open_files(support_ext: &[impl ToString]) -> Result<Vec<String>, String> {
+    println!("Opening files...");
+    let picked_files = rfd::AsyncFileDialog::new()
+        .set_title("Open files...")
+        .add_filter("Supported", support_ext)
+        .add_filter("All files", &["*"])
+        .pick_files()
+        .await;
+
+    // Handle the case where the user cancels the dialog
+    let picked_files = match picked_files {
+        Some(files) if !files.is_empty() => files,
+        _ => return Err("No file was selected.".to_string()),
+    };
+
+    // Handle the case where a path is not valid
+    picked_files
+        .iter()
+        .map(|file| match file.path().to_str() {
+            Some(path) => Ok(path.to_string()),
+            None => Err("File path is not valid UTF-8.".to_string()),
+        })
+        .collect()
+}
+
+async fn open_folder() -> Result<String, String> {
+    println!("Opening folder...");
+    let picked_folder = rfd::AsyncFileDialog::new()
+        .set_title("Choose folder...")
+        .pick_folder()
+        .await;
+
+    // Handle the case where the user cancels the dialog
+    let picked_folder = match picked_folder {
+        Some(folder) => folder,
+        None => return Err("No folder was selected.".to_string()),
+    };
+
+    // Handle the case where the path is not valid
+    let path = match picked_folder.path().to_str() {
+        Some(path) => path,
+        None => return Err("Folder path is not valid UTF-8.".to_string()),
+    };
+
+    Ok(path.to_string())
+}
+
 async fn save_file(support_ext: &[impl ToString]) -> Result<String, String> {
     println!("Opening file...");
     let picked_file = rfd::AsyncFileDialog::new()
@@ -659,6 +3135,11 @@ impl fmt::Display for Rotation {
     }
 }
 
+impl fmt::Display for SpeedPreset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x", self.0)
+    }
+}
 
 // impl std::fmt::Display for Rotation {
 //     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
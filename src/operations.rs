@@ -0,0 +1,520 @@
+use crate::engine::audio::{adjust_audio, detect_silence, remap_channels, remove_silence, AudioAdjust, SilentRange};
+use crate::engine::capabilities::Requirements;
+use crate::engine::chain::{run_chain, validate, Step};
+use crate::engine::command;
+use crate::engine::convert::{convert, stream_codec};
+use crate::engine::custom::{run_custom, CustomArgs};
+use crate::engine::encoding::{AudioCodec, EncodingSettings, RateControl};
+use crate::engine::loudness::{analyze, normalize, LoudnessTarget};
+use crate::engine::probe::{probe, Probe, Stream};
+use crate::engine::streams::{remux_tracks, Track};
+use crate::engine::write_atomically;
+use crate::engine::{animation, extract_audio, extract_frames, mute, rotate, slideshow, speed, AnimationFormat, AnimationOptions, AudioTarget, FrameSelection, ImageFormat, SlideshowMode, SlideshowOptions};
+use crate::presets::{FrameMode, Preset, RateMode};
+use crate::queue::{JobOutput, Operation};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+// Every action, by the name presets and the command line use
+pub const ACTIONS: [&str; 16] = [
+    "mute",
+    "rotate",
+    "speed",
+    "animation",
+    "frames",
+    "slideshow",
+    "extract_audio",
+    "normalize",
+    "analyze",
+    "adjust_audio",
+    "remove_silence",
+    "channels",
+    "streams",
+    "convert",
+    "chain",
+    "custom",
+];
+
+// What an operation reads besides the preset: the files picked in the window or
+// on the command line, and what was learned about them.
+#[derive(Debug, Clone, Default)]
+pub struct Inputs {
+    pub source: String,
+    pub probe: Option<Probe>, // of `source`
+    pub extract_stream: Option<Stream>,
+    pub slide_images: Vec<String>,
+    pub slide_audio: Option<String>,
+    pub silences: Vec<SilentRange>, // detected in `source`
+    pub track_inputs: Vec<String>,
+    pub tracks: Vec<Track>,
+}
+
+impl Inputs {
+    // Probes `source` and picks the streams the operations start with
+    pub fn probed(source: &str) -> Result<Inputs, io::Error> {
+        let probe = probe(source)?;
+        Ok(Inputs {
+            source: source.to_string(),
+            extract_stream: probe.audio_streams().first().map(|s| (*s).clone()),
+            track_inputs: vec![source.to_string()],
+            tracks: probe
+                .streams
+                .iter()
+                .filter(|s| s.codec_type == "audio" || s.codec_type == "subtitle")
+                .map(|s| Track::from_stream(0, s))
+                .collect(),
+            probe: Some(probe),
+            ..Inputs::default()
+        })
+    }
+
+    // Offers the audio and subtitle tracks of another file to the stream manager,
+    // none of them becomes a default track
+    pub fn add_track_input(&mut self, path: &str) -> Result<(), io::Error> {
+        let probe = probe(path)?;
+        let input = self.track_inputs.len();
+        self.track_inputs.push(path.to_string());
+        self.tracks.extend(
+            probe
+                .streams
+                .iter()
+                .filter(|s| s.codec_type == "audio" || s.codec_type == "subtitle")
+                .map(|s| Track { default: false, ..Track::from_stream(input, s) }),
+        );
+        Ok(())
+    }
+}
+
+// Slideshows and the stream manager combine their inputs, they can't run per file
+pub fn combines_inputs(action: &str) -> bool {
+    matches!(action, "slideshow" | "streams")
+}
+
+// Which streams the action re-encodes, (video, audio)
+pub fn encodes(preset: &Preset, inputs: &Inputs) -> (bool, bool) {
+    match preset.action.as_str() {
+        "rotate" => (true, false),
+        "speed" => (true, !preset.speed_drop_audio),
+        "slideshow" => (true, inputs.slide_audio.is_some()),
+        "remove_silence" | "convert" | "chain" | "custom" => (true, true),
+        "normalize" | "adjust_audio" | "channels" => (false, true),
+        _ => (false, false),
+    }
+}
+
+// What the action needs from the ffmpeg build
+pub fn requirements(preset: &Preset, inputs: &Inputs) -> Requirements {
+    let mut required = Requirements::default();
+    match preset.action.as_str() {
+        "rotate" => required.filters = vec!["transpose"],
+        "speed" => required.filters = vec!["setpts", "atempo"],
+        "animation" => match preset.anim_format {
+            AnimationFormat::Gif => {
+                required.encoders = vec!["gif"];
+                required.filters = vec!["fps", "scale", "palettegen", "paletteuse"];
+            }
+            AnimationFormat::WebP => {
+                required.encoders = vec!["libwebp_anim"];
+                required.filters = vec!["fps", "scale"];
+            }
+        },
+        "frames" => {
+            required.encoders = vec![match preset.frames_format {
+                ImageFormat::Png => "png",
+                ImageFormat::Jpg => "mjpeg",
+                ImageFormat::WebP => "libwebp",
+            }];
+            if preset.frames_mode == FrameMode::Interval {
+                required.filters = vec!["fps"];
+            }
+        }
+        "slideshow" => {
+            required.filters = vec!["scale", "pad", "setsar", "format"];
+            if preset.slide_mode == SlideshowMode::Slideshow {
+                required.filters.push(if preset.slide_ken_burns { "zoompan" } else { "loop" });
+                required.filters.push("xfade");
+                required.filters.push("concat");
+            }
+        }
+        "extract_audio" => match preset.extract_target {
+            AudioTarget::Copy => {}
+            AudioTarget::Mp3 => required.encoders = vec!["libmp3lame"],
+            AudioTarget::Flac => required.encoders = vec!["flac"],
+            AudioTarget::Wav => required.encoders = vec!["pcm_s16le"],
+            AudioTarget::Opus => required.encoders = vec!["libopus"],
+        },
+        "normalize" => required.filters = vec!["loudnorm"],
+        "analyze" => required.filters = vec!["ebur128", "astats", "volumedetect"],
+        "adjust_audio" => required.filters = vec!["volume", "afade", "adelay"],
+        "remove_silence" => required.filters = vec!["silencedetect", "select", "aselect"],
+        "channels" => required.filters = vec!["pan", "channelmap"],
+        "chain" => {
+            for step in &preset.chain_steps {
+                required.filters.extend(step.kind.filters());
+            }
+            required.filters.sort();
+            required.filters.dedup();
+        }
+        "convert" => {
+            let encoding = EncodingSettings {
+                video_codec: preset.enc_video_codec,
+                audio_codec: preset.enc_audio_codec,
+                ..EncodingSettings::default()
+            };
+            required.muxers = vec![preset.container_format.muxer()];
+            for stream in inputs.probe.iter().flat_map(|p| p.streams.iter()) {
+                match stream_codec(preset.container_format, stream, &encoding) {
+                    Some("copy") | None => {}
+                    Some(encoder) => required.encoders.push(encoder),
+                }
+            }
+        }
+        _ => {}
+    }
+
+    let (video, audio) = encodes(preset, inputs);
+    if video && preset.enc_video_codec.encodes() {
+        required.encoders.extend(preset.enc_video_codec.encoder());
+    }
+    if audio && preset.enc_audio_codec != AudioCodec::Copy {
+        required.encoders.extend(preset.enc_audio_codec.encoder());
+    }
+    required.encoders.sort();
+    required.encoders.dedup();
+    required
+}
+
+// Extension the output has to carry for the action, if it is fixed
+pub fn output_extension(preset: &Preset, inputs: &Inputs) -> Option<&'static str> {
+    match preset.action.as_str() {
+        "animation" => Some(preset.anim_format.extension()),
+        "convert" => Some(preset.container_format.extension()),
+        "extract_audio" => {
+            let codec = inputs.extract_stream.as_ref().map(|s| s.codec_name.as_str()).unwrap_or_default();
+            Some(preset.extract_target.extension(codec))
+        }
+        _ => None,
+    }
+}
+
+// Output path of the `index`th batch file, from the folder and filename template
+pub fn batch_output(preset: &Preset, inputs: &Inputs, batch_dir: &str, input: &str, index: usize) -> String {
+    let path = Path::new(input);
+    let name = path.file_stem().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let file_name = preset
+        .batch_template
+        .trim()
+        .replace("{name}", &name)
+        .replace("{action}", &preset.action)
+        .replace("{index}", &(index + 1).to_string());
+    let output = Path::new(batch_dir).join(file_name);
+    // frames go into one folder per input
+    if preset.action == "frames" {
+        return output.to_string_lossy().to_string();
+    }
+    let ext = output_extension(preset, inputs)
+        .or_else(|| path.extension().and_then(|ext| ext.to_str()))
+        .unwrap_or("mp4");
+    output.with_extension(ext).to_string_lossy().to_string()
+}
+
+// Whether running would replace something: a file, or frames already in the folder
+pub fn output_taken(action: &str, output: &str) -> bool {
+    match action {
+        "analyze" => false,
+        "frames" => fs::read_dir(output).is_ok_and(|mut entries| entries.next().is_some()),
+        _ => Path::new(output).exists(),
+    }
+}
+
+// Encoder settings from the codec panel
+pub fn encoding(preset: &Preset) -> Result<EncodingSettings, String> {
+    let rate_control = match non_empty(&preset.enc_quality) {
+        None => None,
+        Some(value) => match (preset.enc_rate_mode, value.parse::<u32>()) {
+            (RateMode::Crf, Ok(crf)) => Some(RateControl::Crf(crf)),
+            (RateMode::Bitrate, Ok(kbps)) if kbps > 0 => Some(RateControl::Bitrate(kbps)),
+            _ => return Err("Invalid CRF or bitrate".to_string()),
+        },
+    };
+    let audio_bitrate = match preset.enc_audio_bitrate.trim().parse::<u32>() {
+        Ok(kbps) if kbps > 0 => Some(kbps),
+        _ if !preset.enc_audio_codec.has_bitrate() => None,
+        _ => return Err("Invalid audio bitrate".to_string()),
+    };
+    Ok(EncodingSettings {
+        video_codec: preset.enc_video_codec,
+        preset: preset.enc_preset.clone(),
+        rate_control: rate_control.filter(|_| preset.enc_video_codec.encodes()),
+        pixel_format: non_empty(&preset.enc_pixel_format),
+        profile: preset.enc_profile.clone(),
+        level: non_empty(&preset.enc_level),
+        audio_codec: preset.enc_audio_codec,
+        audio_bitrate,
+    })
+}
+
+// Parsed chain steps, validated against the probed input when there is one
+pub fn chain(preset: &Preset, probe: Option<&Probe>) -> Result<Vec<Step>, String> {
+    let steps = preset.chain_steps.iter().map(|step| step.to_step()).collect::<Result<Vec<_>, _>>()?;
+    if let Some(probe) = probe {
+        let has_video = !probe.streams_of("video").is_empty();
+        validate(&steps, has_video, !probe.audio_streams().is_empty()).map_err(|e| e.to_string())?;
+    }
+    Ok(steps)
+}
+
+// Validates the settings of the preset's action and captures them in an operation
+pub fn operation(preset: &Preset, inputs: &Inputs) -> Result<Operation, String> {
+    let operation: Operation = match preset.action.as_str() {
+        "mute" => Arc::new(|input, output| mute(input, output).map(|_| JobOutput::done("Successfully muted the audio."))),
+        "rotate" => {
+            let encoding = encoding(preset)?;
+            Arc::new(move |input, output| {
+                rotate(input, output, "transpose=1", &encoding).map(|_| JobOutput::done("Successfully rotated the video."))
+            })
+        }
+        "speed" => {
+            let factor = match preset.speed_factor.trim().trim_end_matches('x').parse::<f64>() {
+                Ok(factor) if factor > 0.0 => factor,
+                _ => return Err("Invalid speed factor".to_string()),
+            };
+            let drop_audio = preset.speed_drop_audio;
+            let encoding = encoding(preset)?;
+            Arc::new(move |input, output| {
+                speed(input, output, factor, drop_audio, &encoding)
+                    .map(|_| JobOutput::done("Successfully changed the video speed."))
+            })
+        }
+        "animation" => {
+            let (Ok(fps), Ok(width), Ok(loop_count)) = (
+                preset.anim_fps.trim().parse::<u32>(),
+                preset.anim_width.trim().parse::<u32>(),
+                preset.anim_loop.trim().parse::<u32>(),
+            ) else {
+                return Err("Invalid fps, width or loop count".to_string());
+            };
+            if fps == 0 || width == 0 {
+                return Err("The fps and width must be above 0".to_string());
+            }
+            let options = AnimationOptions {
+                format: preset.anim_format,
+                fps,
+                width,
+                dither: preset.anim_dither,
+                loop_count,
+                start: non_empty(&preset.anim_start),
+                end: non_empty(&preset.anim_end),
+            };
+            Arc::new(move |input, output| {
+                animation(input, output, &options).map(|size| {
+                    JobOutput::done(format!("Successfully exported the {} ({}).", options.format, human_size(size)))
+                })
+            })
+        }
+        "frames" => {
+            let selection = match preset.frames_mode {
+                FrameMode::EveryFrame => FrameSelection::EveryFrame,
+                FrameMode::Keyframes => FrameSelection::Keyframes,
+                FrameMode::Single => FrameSelection::Single(preset.frames_timestamp.trim().to_string()),
+                FrameMode::Interval => match preset.frames_interval.trim().parse::<f64>() {
+                    Ok(seconds) if seconds > 0.0 => FrameSelection::Interval(seconds),
+                    _ => return Err("Invalid frame interval".to_string()),
+                },
+            };
+            let pattern = preset.frames_pattern.trim().to_string();
+            if pattern.is_empty() {
+                return Err("Invalid filename pattern".to_string());
+            }
+            let format = preset.frames_format;
+            Arc::new(move |input, output| {
+                extract_frames(input, output, &pattern, format, &selection)
+                    .map(|_| JobOutput::done("Successfully extracted the frames."))
+            })
+        }
+        "slideshow" => {
+            let size = preset.slide_size.trim().split_once('x').map(|(w, h)| (w.parse::<u32>(), h.parse::<u32>()));
+            let Some((Ok(width), Ok(height))) = size else {
+                return Err("Invalid size, use WIDTHxHEIGHT".to_string());
+            };
+            let (Ok(fps), Ok(duration), Ok(crossfade)) = (
+                preset.slide_fps.trim().parse::<u32>(),
+                preset.slide_duration.trim().parse::<f64>(),
+                preset.slide_crossfade.trim().parse::<f64>(),
+            ) else {
+                return Err("Invalid frame rate, duration or crossfade".to_string());
+            };
+            let options = SlideshowOptions {
+                mode: preset.slide_mode,
+                fps,
+                width,
+                height,
+                duration,
+                crossfade,
+                ken_burns: preset.slide_ken_burns,
+                audio: inputs.slide_audio.clone(),
+            };
+            let encoding = encoding(preset)?;
+            let images = inputs.slide_images.clone();
+            // the images stand in for the input
+            Arc::new(move |_, output| {
+                slideshow(&images, output, &options, &encoding).map(|_| JobOutput::done("Successfully created the video."))
+            })
+        }
+        "extract_audio" => {
+            let Some(stream) = inputs.extract_stream.clone() else {
+                return Err("No audio stream selected".to_string());
+            };
+            let bitrate = match preset.extract_bitrate.trim().parse::<u32>() {
+                Ok(bitrate) if bitrate > 0 => bitrate,
+                _ if !preset.extract_target.has_bitrate() => 0,
+                _ => return Err("Invalid bitrate".to_string()),
+            };
+            let target = preset.extract_target;
+            let source = inputs.source.clone();
+            Arc::new(move |input, output| {
+                // other batch files use their first audio stream
+                let index = if input == source {
+                    stream.index
+                } else {
+                    let probe = probe(input)?;
+                    let first = probe.audio_streams().first().map(|s| s.index);
+                    first.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "The input has no audio stream"))?
+                };
+                extract_audio(input, output, index, target, bitrate).map(|_| JobOutput::done("Successfully extracted the audio."))
+            })
+        }
+        "normalize" => {
+            let (Ok(integrated), Ok(true_peak), Ok(range)) = (
+                preset.norm_integrated.trim().parse::<f64>(),
+                preset.norm_true_peak.trim().parse::<f64>(),
+                preset.norm_range.trim().parse::<f64>(),
+            ) else {
+                return Err("Invalid loudness target".to_string());
+            };
+            let target = LoudnessTarget { integrated, true_peak, range };
+            let encoding = encoding(preset)?;
+            Arc::new(move |input, output| {
+                normalize(input, output, &target, &encoding).map(|_| {
+                    JobOutput::done(format!("Successfully normalized the audio to {} LUFS.", integrated))
+                })
+            })
+        }
+        "analyze" => Arc::new(|input, _| analyze(input).map(JobOutput::Report)),
+        "adjust_audio" => {
+            let (Ok(gain_db), Ok(fade_in), Ok(fade_out), Ok(offset)) = (
+                preset.adjust_gain.trim().parse::<f64>(),
+                preset.adjust_fade_in.trim().parse::<f64>(),
+                preset.adjust_fade_out.trim().parse::<f64>(),
+                preset.adjust_offset.trim().parse::<f64>(),
+            ) else {
+                return Err("Invalid gain, fade or offset".to_string());
+            };
+            let adjust = AudioAdjust { gain_db, fade_in, fade_out, offset };
+            let encoding = encoding(preset)?;
+            Arc::new(move |input, output| {
+                adjust_audio(input, output, &adjust, &encoding).map(|_| JobOutput::done("Successfully adjusted the audio."))
+            })
+        }
+        "remove_silence" => {
+            let Ok(padding) = preset.silence_padding.trim().parse::<f64>() else {
+                return Err("Invalid padding".to_string());
+            };
+            let (Ok(threshold), Ok(min_duration)) = (
+                preset.silence_threshold.trim().parse::<f64>(),
+                preset.silence_min.trim().parse::<f64>(),
+            ) else {
+                return Err("Invalid threshold or minimum duration".to_string());
+            };
+            let encoding = encoding(preset)?;
+            let (source, detected) = (inputs.source.clone(), inputs.silences.clone());
+            Arc::new(move |input, output| {
+                // the detected ranges only fit the previewed input, the job detects the rest
+                let silences = if input == source && !detected.is_empty() {
+                    detected.clone()
+                } else {
+                    detect_silence(input, threshold, min_duration)?
+                };
+                if silences.is_empty() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "No silence found, try a higher threshold"));
+                }
+                remove_silence(input, output, &silences, padding, &encoding)
+                    .map(|_| JobOutput::done(format!("Successfully removed {} silent parts.", silences.len())))
+            })
+        }
+        "channels" => {
+            let mapping = preset.channel_mapping;
+            let encoding = encoding(preset)?;
+            Arc::new(move |input, output| {
+                let probe = probe(input)?;
+                let layout = probe.audio_streams().first().and_then(|s| s.channel_layout.clone()).unwrap_or_default();
+                remap_channels(input, output, mapping, &layout, &encoding)
+                    .map(|_| JobOutput::done("Successfully remapped the audio channels."))
+            })
+        }
+        "streams" => {
+            let (inputs, tracks) = (inputs.track_inputs.clone(), inputs.tracks.clone());
+            // the track list names its own inputs
+            Arc::new(move |_, output| {
+                remux_tracks(&inputs, output, &tracks).map(|_| JobOutput::done("Successfully remuxed the streams."))
+            })
+        }
+        "chain" => {
+            let steps = chain(preset, inputs.probe.as_ref())?;
+            let encoding = encoding(preset)?;
+            Arc::new(move |input, output| {
+                run_chain(input, output, &steps, &encoding)
+                    .map(|_| JobOutput::done(format!("Successfully applied {} steps.", steps.len())))
+            })
+        }
+        "custom" => {
+            let input_options = command::split(&preset.custom_input_options).map_err(|e| format!("Invalid options: {}", e))?;
+            let output_options = command::split(&preset.custom_output_options).map_err(|e| format!("Invalid options: {}", e))?;
+            let custom = CustomArgs {
+                input_options,
+                output_options,
+                filter_complex: non_empty(&preset.custom_filter),
+            };
+            let encoding = encoding(preset)?;
+            Arc::new(move |input, output| {
+                run_custom(input, output, &custom, &encoding).map(|_| JobOutput::done("Successfully ran the custom command."))
+            })
+        }
+        "convert" => {
+            if inputs.probe.is_none() {
+                return Err("Unable to read the input streams".to_string());
+            }
+            let container = preset.container_format;
+            let encoding = encoding(preset)?;
+            Arc::new(move |input, output| {
+                let probe = probe(input)?;
+                convert(input, output, &probe, container, &encoding)
+                    .map(|_| JobOutput::done(format!("Successfully converted to {}.", container)))
+            })
+        }
+        action => return Err(format!("Unknown operation {}", action)),
+    };
+    // frames fill a folder and the analysis writes nothing
+    if matches!(preset.action.as_str(), "frames" | "analyze") {
+        return Ok(operation);
+    }
+    Ok(Arc::new(move |input, output| write_atomically(input, output, |temp| operation(input, temp))))
+}
+
+pub fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+fn human_size(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, units[unit])
+}
@@ -0,0 +1,486 @@
+use crate::engine::audio::{AudioAdjust, ChannelMapping};
+use crate::engine::chain::{Position, Step, Turn};
+use crate::engine::convert::ContainerFormat;
+use crate::engine::encoding::{AudioCodec, VideoCodec};
+use crate::engine::loudness::LoudnessPreset;
+use crate::engine::{AnimationFormat, AudioTarget, Dither, ImageFormat, SlideshowMode};
+use crate::settings::config_dir;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Every control of the operations and the codec panel, as saved in a preset file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    pub action: String,
+    pub speed_factor: String,
+    pub speed_drop_audio: bool,
+    pub anim_format: AnimationFormat,
+    pub anim_fps: String,
+    pub anim_width: String,
+    pub anim_dither: Dither,
+    pub anim_loop: String,
+    pub anim_start: String,
+    pub anim_end: String,
+    pub frames_mode: FrameMode,
+    pub frames_format: ImageFormat,
+    pub frames_interval: String,
+    pub frames_timestamp: String,
+    pub frames_pattern: String,
+    pub slide_mode: SlideshowMode,
+    pub slide_fps: String,
+    pub slide_size: String,
+    pub slide_duration: String,
+    pub slide_crossfade: String,
+    pub slide_ken_burns: bool,
+    pub extract_target: AudioTarget,
+    pub extract_bitrate: String,
+    pub norm_preset: LoudnessPreset,
+    pub norm_integrated: String,
+    pub norm_true_peak: String,
+    pub norm_range: String,
+    pub adjust_gain: String,
+    pub adjust_fade_in: String,
+    pub adjust_fade_out: String,
+    pub adjust_offset: String,
+    pub silence_threshold: String,
+    pub silence_min: String,
+    pub silence_padding: String,
+    pub channel_mapping: ChannelMapping,
+    pub container_format: ContainerFormat,
+    pub enc_video_codec: VideoCodec,
+    pub enc_preset: Option<String>,
+    pub enc_rate_mode: RateMode,
+    pub enc_quality: String,
+    pub enc_pixel_format: String,
+    pub enc_profile: Option<String>,
+    pub enc_level: String,
+    pub enc_audio_codec: AudioCodec,
+    pub enc_audio_bitrate: String,
+    pub batch_template: String,
+    pub chain_steps: Vec<ChainStep>,
+    pub custom_input_options: String,
+    pub custom_output_options: String,
+    pub custom_filter: String,
+}
+
+impl Preset {
+    // The settings a fresh window starts with
+    pub fn initial() -> Preset {
+        let mut preset = Preset {
+            action: "mute".to_string(),
+            speed_factor: "2".to_string(),
+            anim_format: AnimationFormat::Gif,
+            anim_fps: "12".to_string(),
+            anim_width: "480".to_string(),
+            anim_dither: Dither::Sierra2_4a,
+            anim_loop: "0".to_string(),
+            frames_mode: FrameMode::EveryFrame,
+            frames_format: ImageFormat::Png,
+            frames_interval: "1".to_string(),
+            frames_timestamp: "00:00:00".to_string(),
+            frames_pattern: "frame_%04d".to_string(),
+            slide_mode: SlideshowMode::Sequence,
+            slide_fps: "25".to_string(),
+            slide_size: "1920x1080".to_string(),
+            slide_duration: "3".to_string(),
+            slide_crossfade: "1".to_string(),
+            extract_target: AudioTarget::Copy,
+            extract_bitrate: "192".to_string(),
+            norm_preset: LoudnessPreset::Streaming,
+            adjust_gain: "0".to_string(),
+            adjust_fade_in: "0".to_string(),
+            adjust_fade_out: "0".to_string(),
+            adjust_offset: "0".to_string(),
+            silence_threshold: "-35".to_string(),
+            silence_min: "0.5".to_string(),
+            silence_padding: "0.1".to_string(),
+            channel_mapping: ChannelMapping::MonoToStereo,
+            container_format: ContainerFormat::Mkv,
+            enc_video_codec: VideoCodec::Auto,
+            enc_rate_mode: RateMode::Crf,
+            enc_audio_codec: AudioCodec::Auto,
+            enc_audio_bitrate: "192".to_string(),
+            batch_template: "{name}_{action}".to_string(),
+            ..Preset::default()
+        };
+        if let Some(target) = preset.norm_preset.target() {
+            preset.norm_integrated = target.integrated.to_string();
+            preset.norm_true_peak = target.true_peak.to_string();
+            preset.norm_range = target.range.to_string();
+        }
+        preset
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum FrameMode {
+    #[default]
+    EveryFrame,
+    Interval,
+    Keyframes,
+    Single,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum RateMode {
+    #[default]
+    Crf,
+    Bitrate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum StepKind {
+    Trim,
+    Crop,
+    Rotate,
+    Resize,
+    Watermark,
+    Text,
+    Speed,
+    Audio,
+}
+
+// A chain step as edited in the UI, `fields` follow StepKind::fields
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainStep {
+    pub kind: StepKind,
+    pub fields: Vec<String>,
+    pub turn: Turn,
+    pub position: Position,
+}
+
+impl StepKind {
+    pub const ALL: [StepKind; 8] = [
+        StepKind::Trim,
+        StepKind::Crop,
+        StepKind::Rotate,
+        StepKind::Resize,
+        StepKind::Watermark,
+        StepKind::Text,
+        StepKind::Speed,
+        StepKind::Audio,
+    ];
+
+    // (placeholder, initial value) of every text field of the step
+    pub fn fields(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            StepKind::Trim => &[("start", "00:00:00"), ("end", "")],
+            StepKind::Crop => &[("width", "1280"), ("height", "720"), ("x", "0"), ("y", "0")],
+            StepKind::Rotate => &[],
+            StepKind::Resize => &[("width", "1280"), ("height", "-2")],
+            StepKind::Watermark => &[("image file", ""), ("margin", "20"), ("opacity", "0.8")],
+            StepKind::Text => &[("text", ""), ("size", "36")],
+            StepKind::Speed => &[("factor", "2")],
+            StepKind::Audio => &[("gain dB", "0"), ("fade in", "0"), ("fade out", "0"), ("offset", "0")],
+        }
+    }
+
+    pub fn filters(&self) -> &'static [&'static str] {
+        match self {
+            StepKind::Trim => &[],
+            StepKind::Crop => &["crop"],
+            StepKind::Rotate => &["transpose", "hflip", "vflip"],
+            StepKind::Resize => &["scale"],
+            StepKind::Watermark => &["overlay", "format", "colorchannelmixer"],
+            StepKind::Text => &["drawtext"],
+            StepKind::Speed => &["setpts", "atempo"],
+            StepKind::Audio => &["adelay", "volume", "afade"],
+        }
+    }
+}
+
+impl ChainStep {
+    pub fn new(kind: StepKind) -> ChainStep {
+        ChainStep {
+            kind,
+            fields: kind.fields().iter().map(|(_, value)| value.to_string()).collect(),
+            turn: Turn::Clockwise,
+            position: Position::BottomRight,
+        }
+    }
+
+    // Preset files can hold any number of fields, the missing ones get their
+    // initial value and extra ones are dropped so every index of the kind exists
+    pub fn with_all_fields(mut self) -> ChainStep {
+        let initial = self.kind.fields();
+        self.fields.truncate(initial.len());
+        let present = self.fields.len();
+        self.fields.extend(initial[present..].iter().map(|(_, value)| value.to_string()));
+        self
+    }
+
+    pub fn to_step(&self) -> Result<Step, String> {
+        let field = |i: usize| self.fields.get(i).map_or("", String::as_str);
+        let number = |i: usize| {
+            field(i)
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| format!("{}: invalid {}", self.kind, self.kind.fields()[i].0))
+        };
+        let step = match self.kind {
+            StepKind::Trim => {
+                let time = |i: usize| Some(field(i).trim().to_string()).filter(|time| !time.is_empty());
+                Step::Trim { start: time(0), end: time(1) }
+            }
+            StepKind::Crop => Step::Crop {
+                width: number(0)? as u32,
+                height: number(1)? as u32,
+                x: number(2)? as u32,
+                y: number(3)? as u32,
+            },
+            StepKind::Rotate => Step::Rotate(self.turn),
+            StepKind::Resize => Step::Resize { width: number(0)? as i32, height: number(1)? as i32 },
+            StepKind::Watermark => Step::Watermark {
+                image: field(0).trim().to_string(),
+                position: self.position,
+                margin: number(1)? as u32,
+                opacity: number(2)?,
+            },
+            StepKind::Text => Step::Text { text: field(0).to_string(), size: number(1)? as u32, position: self.position },
+            StepKind::Speed => Step::Speed(number(0)?),
+            StepKind::Audio => Step::Audio(AudioAdjust {
+                gain_db: number(0)?,
+                fade_in: number(1)?,
+                fade_out: number(2)?,
+                offset: number(3)?,
+            }),
+        };
+        Ok(step)
+    }
+}
+
+impl fmt::Display for StepKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            StepKind::Trim => "Trim",
+            StepKind::Crop => "Crop",
+            StepKind::Rotate => "Rotate",
+            StepKind::Resize => "Resize",
+            StepKind::Watermark => "Watermark",
+            StepKind::Text => "Text",
+            StepKind::Speed => "Speed",
+            StepKind::Audio => "Audio adjust",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl fmt::Display for RateMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            RateMode::Crf => "Constant quality (CRF)",
+            RateMode::Bitrate => "Target bitrate",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl fmt::Display for FrameMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            FrameMode::EveryFrame => "Every frame",
+            FrameMode::Interval => "One frame every N seconds",
+            FrameMode::Keyframes => "Keyframes only",
+            FrameMode::Single => "Single frame at timestamp",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+fn presets_dir() -> Result<PathBuf, io::Error> {
+    config_dir()
+        .map(|dir| dir.join("presets"))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No config folder found"))
+}
+
+// Preset names become file names, anything but letters, digits, '-' and '_' is replaced
+fn preset_file(name: &str) -> Result<PathBuf, io::Error> {
+    let file_name: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    if file_name.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "The preset needs a name"));
+    }
+    Ok(presets_dir()?.join(format!("{}.json", file_name)))
+}
+
+// A preset file in the presets folder and the name saved inside it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedPreset {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl fmt::Display for SavedPreset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+// The saved presets, sorted by name
+pub fn saved() -> Vec<SavedPreset> {
+    let Ok(entries) = presets_dir().and_then(fs::read_dir) else {
+        return vec![];
+    };
+    let mut saved: Vec<SavedPreset> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| read_file(&path, &Preset::default()).ok().map(|preset| SavedPreset { name: preset.name, path }))
+        .collect();
+    saved.sort_by(|a, b| a.name.cmp(&b.name));
+    saved
+}
+
+// The saved preset called `name`, or stored in the file `name`.json
+pub fn find(name: &str) -> Result<SavedPreset, io::Error> {
+    let saved = saved();
+    let by_name = saved.iter().find(|preset| preset.name == name.trim());
+    let by_file = || saved.iter().find(|preset| preset.path.file_stem().is_some_and(|stem| stem == name.trim()));
+    by_name
+        .or_else(by_file)
+        .cloned()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No preset named {}", name)))
+}
+
+pub fn load(saved: &SavedPreset, base: &Preset) -> Result<Preset, io::Error> {
+    read_file(&saved.path, base)
+}
+
+pub fn save(preset: &Preset) -> Result<(), io::Error> {
+    save_to(&preset_file(&preset.name)?, preset)
+}
+
+fn save_to(file: &Path, preset: &Preset) -> Result<(), io::Error> {
+    // names that only differ in replaced characters share a file name
+    if let Ok(existing) = read_file(file, &Preset::default()) {
+        if existing.name.trim() != preset.name.trim() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} holds the preset {}, pick another name", file.display(), existing.name),
+            ));
+        }
+    }
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    write_file(file, preset)
+}
+
+pub fn delete(saved: &SavedPreset) -> Result<(), io::Error> {
+    fs::remove_file(&saved.path)
+}
+
+// Reads a preset file, keys missing from it keep their value from `base` so
+// hand written or older presets only need the settings they change.
+pub fn read_file(path: &Path, base: &Preset) -> Result<Preset, io::Error> {
+    let invalid = |e: serde_json::Error| io::Error::new(io::ErrorKind::InvalidData, e);
+    let content: Value = serde_json::from_slice(&fs::read(path)?).map_err(invalid)?;
+    let Value::Object(content) = content else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "A preset must be a JSON object"));
+    };
+    let mut preset = merge(base, content)?;
+    if preset.name.trim().is_empty() {
+        preset.name = path.file_stem().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    }
+    Ok(preset)
+}
+
+// Overwrites the keys of `base` with `values`, chain steps get all the fields of their kind
+pub fn merge(base: &Preset, values: Map<String, Value>) -> Result<Preset, io::Error> {
+    let invalid = |e: serde_json::Error| io::Error::new(io::ErrorKind::InvalidData, e);
+    let mut merged = serde_json::to_value(base).map_err(invalid)?;
+    for (key, value) in values {
+        merged[key] = value;
+    }
+    let mut preset: Preset = serde_json::from_value(merged).map_err(invalid)?;
+    preset.chain_steps = preset.chain_steps.into_iter().map(ChainStep::with_all_fields).collect();
+    Ok(preset)
+}
+
+pub fn write_file(path: &Path, preset: &Preset) -> Result<(), io::Error> {
+    let content = serde_json::to_string_pretty(preset).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    fs::write(path, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn merged_steps_get_every_field_of_their_kind() {
+        let mut step = serde_json::to_value(ChainStep::new(StepKind::Crop)).unwrap();
+        step["fields"] = json!(["100"]);
+        let values = Map::from_iter([("chain_steps".to_string(), json!([step]))]);
+        let preset = merge(&Preset::initial(), values).unwrap();
+        assert_eq!(preset.chain_steps[0].fields, ["100", "720", "0", "0"]);
+        assert_eq!(preset.chain_steps[0].to_step(), Ok(Step::Crop { width: 100, height: 720, x: 0, y: 0 }));
+    }
+
+    #[test]
+    fn short_steps_fail_instead_of_panicking() {
+        let step = ChainStep { fields: vec!["100".to_string()], ..ChainStep::new(StepKind::Crop) };
+        assert_eq!(step.to_step(), Err("Crop: invalid height".to_string()));
+    }
+
+    fn temp_preset(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("owl-presets-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
+
+    #[test]
+    fn missing_keys_keep_their_base_value() {
+        let values = Map::from_iter([("speed_factor".to_string(), json!("4"))]);
+        let merged = merge(&Preset::initial(), values).unwrap();
+        assert_eq!(merged, Preset { speed_factor: "4".to_string(), ..Preset::initial() });
+
+        let file = temp_preset("Old_preset.json");
+        fs::write(&file, r#"{"action": "speed", "anim_fps": "24"}"#).unwrap();
+        let preset = read_file(&file, &Preset::initial()).unwrap();
+        assert_eq!((preset.action.as_str(), preset.anim_fps.as_str()), ("speed", "24"));
+        assert_eq!(preset.batch_template, Preset::initial().batch_template);
+        // an unnamed preset is called after its file
+        assert_eq!(preset.name, "Old_preset");
+    }
+
+    #[test]
+    fn read_file_refuses_invalid_presets() {
+        let file = temp_preset("invalid.json");
+        fs::write(&file, "[1, 2]").unwrap();
+        assert_eq!(read_file(&file, &Preset::initial()).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        fs::write(&file, r#"{"speed_factor": 4}"#).unwrap();
+        assert_eq!(read_file(&file, &Preset::initial()).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn save_keeps_presets_sharing_a_file_name_apart() {
+        let file = temp_preset("My_preset.json");
+        let _ = fs::remove_file(&file);
+        let preset = |name: &str| Preset { name: name.to_string(), ..Preset::initial() };
+        save_to(&file, &preset("My preset")).unwrap();
+        // saving it again replaces it, another name mapping to the same file doesn't
+        save_to(&file, &Preset { speed_factor: "3".to_string(), ..preset("My preset") }).unwrap();
+        let error = save_to(&file, &preset("My/preset")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(read_file(&file, &Preset::default()).unwrap().speed_factor, "3");
+    }
+
+    #[test]
+    fn with_all_fields_pads_and_cuts_to_the_kind() {
+        let short = ChainStep { fields: vec!["640".to_string()], ..ChainStep::new(StepKind::Resize) };
+        assert_eq!(short.with_all_fields().fields, ["640", "-2"]);
+        let long = ChainStep { fields: vec!["3".to_string(), "extra".to_string()], ..ChainStep::new(StepKind::Speed) };
+        assert_eq!(long.with_all_fields().fields, ["3"]);
+        let empty = ChainStep { fields: vec![], ..ChainStep::new(StepKind::Trim) };
+        assert_eq!(empty.with_all_fields().fields, ["00:00:00", ""]);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::engine::error::FfmpegError;
+use crate::engine::jobs::{with_reporter, JobStatus, LogLevel, Reporter};
+use crate::engine::loudness::LoudnessReport;
+use crate::settings::config_dir;
+use iced::futures::channel::mpsc;
+use iced::Task;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Lines kept in memory per job, the log file has all of them
+const LOG_CAPACITY: usize = 5000;
+// Log files kept in the config folder, the oldest go when a job starts
+const LOG_FILES: usize = 200;
+
+// Runs blocking `work` on its own thread so the window keeps drawing, the task yields its result.
+pub fn in_background<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> Task<T> {
+    let (sender, receiver) = mpsc::unbounded();
+    thread::spawn(move || {
+        let _ = sender.unbounded_send(work());
+    });
+    Task::run(receiver, |value| value)
+}
+
+// A configured operation, called with the input and output path of a job.
+pub type Operation = Arc<dyn Fn(&str, &str) -> Result<JobOutput, io::Error> + Send + Sync>;
+
+#[derive(Debug, Clone)]
+pub enum JobOutput {
+    Message(String),
+    Report(LoudnessReport),
+}
+
+impl JobOutput {
+    pub fn done(message: impl Into<String>) -> JobOutput {
+        JobOutput::Message(message.into())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum JobEvent {
+    Progress(f32),
+    Log(String),
+    Finished(Result<JobOutput, FfmpegError>),
+}
+
+#[derive(Debug, Clone)]
+pub struct LogLine {
+    pub level: LogLevel,
+    pub text: String,
+}
+
+pub struct Job {
+    pub id: usize,
+    pub label: String,
+    pub input: String,
+    pub output: String,
+    pub status: JobStatus,
+    pub progress: f32,
+    pub log: VecDeque<LogLine>,
+    pub log_file: Option<PathBuf>,
+    log_writer: Option<File>,
+    operation: Operation,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Job {
+    pub fn new(id: usize, label: String, input: String, output: String, operation: Operation) -> Job {
+        Job {
+            id,
+            label,
+            input,
+            output,
+            status: JobStatus::Pending,
+            progress: 0.0,
+            log: VecDeque::new(),
+            log_file: None,
+            log_writer: None,
+            operation,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // Runs the operation on its own thread, the task yields its events until it finishes.
+    pub fn start(&mut self) -> Task<JobEvent> {
+        self.status = JobStatus::Running;
+        self.progress = 0.0;
+        self.log.clear();
+        self.open_log_file();
+
+        let (sender, receiver) = mpsc::unbounded();
+        let (operation, input, output) = (self.operation.clone(), self.input.clone(), self.output.clone());
+        let cancelled = self.cancelled.clone();
+        thread::spawn(move || {
+            let (progress, log) = (sender.clone(), sender.clone());
+            let reporter = Reporter {
+                on_progress: Box::new(move |value| {
+                    let _ = progress.unbounded_send(JobEvent::Progress(value));
+                }),
+                on_log: Box::new(move |line| {
+                    let _ = log.unbounded_send(JobEvent::Log(line.to_string()));
+                }),
+                cancelled,
+            };
+            let result = with_reporter(reporter, || operation(&input, &output));
+            let _ = sender.unbounded_send(JobEvent::Finished(result.map_err(|e| FfmpegError::from_io(&e))));
+        });
+        Task::run(receiver, |event| event)
+    }
+
+    // Every run gets a fresh file in the config folder, the job still runs without one
+    fn open_log_file(&mut self) {
+        let started = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+        let Some(dir) = config_dir().map(|dir| dir.join("logs")) else {
+            return;
+        };
+        let file = dir.join(format!("{}-job{}.log", started, self.id));
+        prune_logs(&dir);
+        match fs::create_dir_all(&dir).and_then(|_| File::create(&file)) {
+            Ok(writer) => {
+                self.log_writer = Some(writer);
+                self.log_file = Some(file);
+            }
+            Err(e) => {
+                eprintln!("Unable to create the log file {}: {}", file.display(), e);
+                self.log_writer = None;
+                self.log_file = None;
+            }
+        }
+    }
+
+    pub fn push_log(&mut self, text: String) {
+        if let Some(writer) = self.log_writer.as_mut() {
+            let _ = writeln!(writer, "{}", text);
+        }
+        if self.log.len() == LOG_CAPACITY {
+            self.log.pop_front();
+        }
+        self.log.push_back(LogLine { level: LogLevel::of(&text), text });
+    }
+
+    pub fn close_log(&mut self) {
+        self.log_writer = None;
+    }
+
+    // The whole log, from the file when there is one since the buffer drops old lines
+    pub fn full_log(&self) -> String {
+        if let Some(content) = self.log_file.as_ref().and_then(|file| fs::read_to_string(file).ok()) {
+            return content;
+        }
+        self.log.iter().map(|line| format!("{}\n", line.text)).collect()
+    }
+
+    // Pending jobs are dropped right away, the running ffmpeg of a started one is killed
+    pub fn cancel(&mut self) {
+        match self.status {
+            JobStatus::Pending => self.status = JobStatus::Cancelled,
+            JobStatus::Running => self.cancelled.store(true, Ordering::Relaxed),
+            _ => {}
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+// Leaves room for one more log file under LOG_FILES, removing the least recently written
+fn prune_logs(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut logs: Vec<(SystemTime, PathBuf)> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+        .map(|path| (fs::metadata(&path).and_then(|m| m.modified()).unwrap_or(UNIX_EPOCH), path))
+        .collect();
+    if logs.len() < LOG_FILES {
+        return;
+    }
+    logs.sort();
+    for (_, path) in &logs[..=logs.len() - LOG_FILES] {
+        let _ = fs::remove_file(path);
+    }
+}
@@ -27,7 +27,7 @@ fn parse_icon_map(input: &str) -> HashMap<String, String> {
     let mut icon_map = HashMap::new();
 
     for cap in re.captures_iter(input) {
-        let name = format!("{}", &cap[1]);
+        let name = cap[1].to_string();
         let value = &cap[2];
         icon_map.insert(name, value.to_string());
     }
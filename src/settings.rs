@@ -0,0 +1,58 @@
+use crate::engine::Binaries;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+const APP_DIR: &str = "ffmpeg-owl-ui";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub binaries: Binaries,
+    pub max_jobs: usize, // ffmpeg processes the queue runs at once
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            binaries: Binaries::default(),
+            max_jobs: 1,
+        }
+    }
+}
+
+// Per-user config folder, e.g. ~/.config/ffmpeg-owl-ui or %APPDATA%\ffmpeg-owl-ui
+pub fn config_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    base.map(|base| base.join(APP_DIR))
+}
+
+fn settings_file() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("settings.json"))
+}
+
+// Missing or unreadable settings fall back to the defaults
+pub fn load() -> Settings {
+    settings_file()
+        .and_then(|file| fs::read(file).ok())
+        .and_then(|content| serde_json::from_slice(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save(settings: &Settings) -> Result<(), io::Error> {
+    let file = settings_file().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No config folder found"))?;
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let content = serde_json::to_string_pretty(settings).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    fs::write(file, content)
+}